@@ -106,10 +106,10 @@ def backfill_initial():
 
 
 def update_file():
-    """Updates the data file with the latest candles."""
+    """Updates the data file with the latest candles and returns the new log returns."""
     if not os.path.exists(FILENAME):
         backfill_initial()
-        return
+        return []
 
     existing = pd.read_csv(FILENAME, parse_dates=["open_time"])
     last_time = int(existing["open_time"].max().timestamp() * 1000)
@@ -117,7 +117,7 @@ def update_file():
     new_klines = fetch_candles(startTime=last_time + 1)
     if not new_klines or len(new_klines) <= 1:
         print("No new candles to update.")
-        return
+        return []
 
     last_row = existing.tail(1)
     new_df_raw = klines_to_df(new_klines)
@@ -128,6 +128,7 @@ def update_file():
     updated = updated.sort_values("open_time").tail(WINDOW_SIZE)
     updated.to_csv(FILENAME, index=False)
     print(f"Updated {FILENAME} with {len(new_returns)} new candles. Total stored: {len(updated)}")
+    return new_returns["log_return"].tolist()
 
 
 if __name__ == '__main__':
@@ -7,7 +7,8 @@ from crypto.api.binance import update_file
 DELAY_SECS = 1
 
 class CandleManager:
-    """Runs update_file() once per minute in a background thread."""
+    """Runs update_file() once per minute in a background thread and passes
+    the newly stored log returns to call_back."""
 
     def __init__(self, file_lock, call_back):
         self.file_lock = file_lock
@@ -32,12 +33,16 @@ class CandleManager:
             if self.stop_event.is_set():
                 break
             print("Minute elapsed, updating candles...")
-            self._update_file()
-            self.call_back()
+            new_returns = self._update_file()
+            try:
+                self.call_back(new_returns)
+            except Exception as e:
+                print(f"Error handling new returns: {e}")
 
     def _update_file(self):
         try:
             with self.file_lock:
-                update_file()
+                return update_file()
         except Exception as e:
             print(f"Error updating candle file: {e}")
+            return []
@@ -1,10 +1,5 @@
 import pandas as pd
 import time
-import math
-import pickle
-from pathlib import Path
-import hashlib
-from arch import arch_model
 
 try:
     import garch_monte_carlo
@@ -17,113 +12,47 @@ except ImportError:
 
 FILENAME = "../data/btc_1m_log_returns.csv"
 NUM_SIMULATIONS = 800000
-
-
-class GARCHCache:
-    """Cache GARCH model to avoid refitting"""
-
-    def __init__(self, cache_dir="../cache"):
-        self.cache_dir = Path(cache_dir)
-        self.cache_dir.mkdir(exist_ok=True)
-
-    def get_cache_key(self, log_returns):
-        """Hash based on data length + last value"""
-        key_data = f"{len(log_returns)}_{log_returns.iloc[-1]:.10f}"
-        return hashlib.md5(key_data.encode()).hexdigest()
-
-    def load(self, log_returns):
-        cache_key = self.get_cache_key(log_returns)
-        cache_file = self.cache_dir / f"garch_{cache_key}.pkl"
-
-        if cache_file.exists():
-            with open(cache_file, 'rb') as f:
-                return pickle.load(f)
-        return None
-
-    def save(self, log_returns, garch_res, std_residuals):
-        cache_key = self.get_cache_key(log_returns)
-        cache_file = self.cache_dir / f"garch_{cache_key}.pkl"
-
-        with open(cache_file, 'wb') as f:
-            pickle.dump({
-                'garch_res': garch_res,
-                'std_residuals': std_residuals
-            }, f)
-
-
-def fit_garch_cached(log_returns, cache):
-    """Fit GARCH with caching"""
-    cached = cache.load(log_returns)
-    if cached:
-        print("✅ Using cached GARCH model")
-        return cached['garch_res'], cached['std_residuals']
-
-    print("🔄 Fitting new GARCH model...")
-    scaled_returns = log_returns * 100
-    am = arch_model(scaled_returns, p=1, q=1, vol='Garch', dist='t', rescale=True)
-    res = am.fit(disp='off')
-
-    if not res.convergence_flag:
-        print("⚠️  WARNING: GARCH model did not converge properly!")
-
-    print(res.summary())
-
-    std_resid = res.resid / res.conditional_volatility
-    cache.save(log_returns, res, std_resid.dropna())
-
-    return res, std_resid.dropna()
+VOLATILITY_MODEL = "garch"  # or "gjr" / "egarch" for asymmetric responses, "har" for HAR-RV
 
 
 class FastGARCHSimulator:
     """Optimized simulator - fit once, query many times"""
 
-    def __init__(self, filename=FILENAME):
+    def __init__(self, filename=FILENAME, model=VOLATILITY_MODEL):
         self.filename = filename
-        self.cache = GARCHCache()
 
         # Load data
         self.log_returns = pd.read_csv(filename)['log_return'].dropna()
 
-        # Fit GARCH (cached)
-        self.garch_res, self.std_residuals = fit_garch_cached(
-            self.log_returns, self.cache
-        )
-
-        # Extract parameters once
-        params = self.garch_res.params
-        self.omega = params['omega']
-        self.alpha = params['alpha[1]']
-        self.beta = params['beta[1]']
-        self.residuals_list = self.std_residuals.tolist()
-
-        # Cache state
-        self.last_resid = self.garch_res.resid.iloc[-1]
-        self.last_sigma_sq = self.garch_res.conditional_volatility.iloc[-1] ** 2
-
-        # Diagnostics
-        alpha_beta_sum = self.alpha + self.beta
-        print(f"\n📊 Model: α+β = {alpha_beta_sum:.4f}")
-        if alpha_beta_sum > 0.999:
-            print("   ⚠️  WARNING: Close to non-stationarity")
+        # Fit natively (a few hundred ms, no cache needed)
+        print(f"🔄 Fitting {model} model...")
+        if model == "har":
+            self.fit = garch_monte_carlo.fit_har(self.log_returns.to_numpy())
+            print(self.fit)
+        else:
+            self.fit = garch_monte_carlo.fit_garch(self.log_returns.to_numpy(), model=model)
+            if not self.fit.converged:
+                print("⚠️  WARNING: GARCH model did not converge properly!")
+            print(self.fit)
+
+            # Diagnostics
+            persistence = self.fit.persistence
+            print(f"\n📊 Model: persistence = {persistence:.4f}")
+            if persistence > 0.999:
+                print("   ⚠️  WARNING: Close to non-stationarity")
         print()
 
+        self.simulator = self.fit.simulator()
+
     def get_probability(self, start_price, target_price, horizon_seconds,
                         num_simulations=NUM_SIMULATIONS):
         """Calculate probability using optimized Rust function"""
-        horizon_minutes = max(1, math.ceil(horizon_seconds / 60))
-
-        return garch_monte_carlo.calculate_probability_only(
-            omega=self.omega,
-            alpha=self.alpha,
-            beta=self.beta,
-            last_resid=self.last_resid,
-            last_sigma_sq=self.last_sigma_sq,
-            residuals=self.residuals_list,
+        return self.simulator.probability(
             current_price=start_price,
             target_price=target_price,
-            horizon_minutes=horizon_minutes,
+            horizon_seconds=horizon_seconds,
             num_simulations=num_simulations
-        )
+        ).probability
 
 
 if __name__ == "__main__":
@@ -155,6 +84,28 @@ if __name__ == "__main__":
     print(f"⏱️  Simulation time: {elapsed:.3f}s\n")
     print(f"{elapsed:.3f}s - {prob:.2%}")
 
+    # Same query under Heston stochastic volatility
+    heston = garch_monte_carlo.fit_heston(sim.log_returns.to_numpy())
+    print(f"\n{heston}")
+    heston_prob = heston.simulator().probability(
+        current_price=start_price,
+        target_price=TARGET_PRICE,
+        horizon_seconds=HORIZON_SECONDS,
+        num_simulations=NUM_SIMULATIONS
+    ).probability
+    print(f">>> Heston: {heston_prob:.2%} vs GARCH: {prob:.2%}")
+
+    # Same query under Markov regime switching
+    regimes = garch_monte_carlo.fit_regimes(sim.log_returns.to_numpy(), num_regimes=2)
+    print(f"\n{regimes}")
+    regime_prob = regimes.simulator().probability(
+        current_price=start_price,
+        target_price=TARGET_PRICE,
+        horizon_seconds=HORIZON_SECONDS,
+        num_simulations=NUM_SIMULATIONS
+    ).probability
+    print(f">>> Regime switching: {regime_prob:.2%} vs GARCH: {prob:.2%}")
+
     # Multiple rapid queries
     # print("=" * 60)
     # print("Running multiple rapid queries...")
@@ -0,0 +1,45 @@
+// garch_monte_carlo/src/array.rs
+// float64 arrays in both directions, through NumPy.
+
+use numpy::{PyArray1, PyReadonlyArray1};
+use pyo3::prelude::*;
+
+/// A 1-D float64 input. Contiguous float64 NumPy arrays are borrowed in
+/// place, read-only; other arrays and sequences such as lists are copied.
+pub enum F64Array<'py> {
+    Borrowed(PyReadonlyArray1<'py, f64>),
+    Owned(Vec<f64>),
+}
+
+impl<'py> FromPyObject<'py> for F64Array<'py> {
+    fn extract(ob: &'py PyAny) -> PyResult<Self> {
+        if let Ok(array) = ob.extract::<PyReadonlyArray1<f64>>() {
+            if array.is_contiguous() {
+                return Ok(F64Array::Borrowed(array));
+            }
+            return Ok(F64Array::Owned(array.as_array().to_vec()));
+        }
+        Ok(F64Array::Owned(ob.extract()?))
+    }
+}
+
+impl F64Array<'_> {
+    pub fn as_slice(&self) -> &[f64] {
+        match self {
+            F64Array::Borrowed(array) => array.as_slice().expect("checked contiguous"),
+            F64Array::Owned(values) => values,
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<f64> {
+        match self {
+            F64Array::Borrowed(array) => array.as_slice().expect("checked contiguous").to_vec(),
+            F64Array::Owned(values) => values,
+        }
+    }
+}
+
+/// Hands `values` to Python as a NumPy array without copying them.
+pub fn to_numpy(py: Python<'_>, values: Vec<f64>) -> PyResult<PyObject> {
+    Ok(PyArray1::from_vec(py, values).to_object(py))
+}
@@ -0,0 +1,254 @@
+// garch_monte_carlo/src/block.rs
+// Block resampling of the shock history and automatic block lengths.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::prelude::*;
+
+/// How consecutive simulated minutes pick shocks from the history.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) enum Blocks {
+    /// Every minute draws an independent index.
+    #[default]
+    Iid,
+    /// Politis-Romano stationary bootstrap: runs of consecutive shocks with
+    /// geometric lengths of this mean.
+    Stationary { mean_length: f64 },
+    /// Circular block bootstrap: runs of exactly this many shocks, wrapping
+    /// from the end of the history to its start.
+    Circular { length: usize },
+}
+
+/// Position of one path in its current block.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Cursor {
+    index: usize,
+    /// Shocks left in the current block; zero starts a new one.
+    left: usize,
+}
+
+impl Blocks {
+    /// Parses `"iid"`, `"stationary"` or `"circular"`; the block lengths
+    /// are given or estimated from `shocks` with `optimal_block_length`.
+    pub(crate) fn parse(scheme: &str, length: Option<f64>, shocks: &[f64]) -> PyResult<Self> {
+        if length.is_some_and(|l| l.is_nan() || l < 1.0) {
+            return Err(PyValueError::new_err("block length must be at least 1"));
+        }
+        let auto = || optimal_block_length(shocks, true);
+        match scheme.to_ascii_lowercase().as_str() {
+            "iid" => Ok(Blocks::Iid),
+            "stationary" => Ok(Blocks::Stationary {
+                mean_length: length.unwrap_or_else(|| auto().0),
+            }),
+            "circular" => Ok(Blocks::Circular {
+                length: length.unwrap_or_else(|| auto().1).round() as usize,
+            }),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown block scheme {scheme:?}; expected 'iid', 'stationary' or 'circular'"
+            ))),
+        }
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Blocks::Iid => "iid",
+            Blocks::Stationary { .. } => "stationary",
+            Blocks::Circular { .. } => "circular",
+        }
+    }
+
+    /// Mean block length in shocks.
+    pub(crate) fn mean_length(&self) -> f64 {
+        match *self {
+            Blocks::Iid => 1.0,
+            Blocks::Stationary { mean_length } => mean_length,
+            Blocks::Circular { length } => length as f64,
+        }
+    }
+
+    fn block_length<R: Rng>(&self, rng: &mut R) -> usize {
+        match *self {
+            Blocks::Iid => 1,
+            Blocks::Stationary { mean_length } if mean_length <= 1.0 => 1,
+            Blocks::Stationary { mean_length } => {
+                // Geometric on {1, 2, ...} with success probability 1/mean.
+                let u: f64 = rng.gen();
+                1 + ((1.0 - u).ln() / (1.0 - 1.0 / mean_length).ln()) as usize
+            }
+            Blocks::Circular { length } => length.max(1),
+        }
+    }
+
+    /// Index of the next shock out of `n` for the path at `cursor`. The
+    /// i.i.d. scheme draws exactly one `gen_range` per call, as before
+    /// blocks existed, so seeded results are unchanged.
+    pub(crate) fn next_index<R: Rng>(&self, rng: &mut R, n: usize, cursor: &mut Cursor) -> usize {
+        if cursor.left == 0 {
+            cursor.index = rng.gen_range(0..n);
+            cursor.left = self.block_length(rng);
+        } else {
+            cursor.index = (cursor.index + 1) % n;
+        }
+        cursor.left -= 1;
+        cursor.index
+    }
+}
+
+/// Politis and White (2004) automatic block lengths, with the Patton,
+/// Politis and White (2009) correction, as `(stationary, circular)`.
+/// With `absolute` the lengths are chosen for the dependence in `|x|`,
+/// which is what carries volatility clustering in returns; raw returns are
+/// nearly uncorrelated and would give blocks of about one.
+pub(crate) fn optimal_block_length(values: &[f64], absolute: bool) -> (f64, f64) {
+    let n = values.len();
+    if n < 4 {
+        return (1.0, 1.0);
+    }
+    let series: Vec<f64> = if absolute {
+        values.iter().map(|x| x.abs()).collect()
+    } else {
+        values.to_vec()
+    };
+    let nf = n as f64;
+    let mean = series.iter().sum::<f64>() / nf;
+    let centred: Vec<f64> = series.iter().map(|x| x - mean).collect();
+    let autocovariance = |k: usize| {
+        centred[k..]
+            .iter()
+            .zip(&centred)
+            .map(|(a, b)| a * b)
+            .sum::<f64>()
+            / nf
+    };
+
+    let k_n = ((nf.log10().sqrt()).ceil() as usize).max(5);
+    let m_max = ((nf.sqrt()).ceil() as usize + k_n).min(n - 1);
+    let b_max = (3.0 * nf.sqrt()).min(nf / 3.0).ceil();
+    let gamma: Vec<f64> = (0..=m_max).map(autocovariance).collect();
+    if gamma[0] <= 0.0 {
+        return (1.0, 1.0);
+    }
+
+    // Smallest lag after which K_N autocorrelations in a row are
+    // insignificant.
+    let bound = 2.0 * (nf.log10() / nf).sqrt();
+    let insignificant = |k: usize| (gamma[k] / gamma[0]).abs() < bound;
+    let m_hat = (0..m_max.saturating_sub(k_n))
+        .find(|&m| (m + 1..=m + k_n).all(insignificant))
+        .unwrap_or(m_max);
+    let big_m = (2 * m_hat).clamp(1, m_max);
+
+    let flat_top = |t: f64| {
+        let t = t.abs();
+        if t <= 0.5 {
+            1.0
+        } else if t <= 1.0 {
+            2.0 * (1.0 - t)
+        } else {
+            0.0
+        }
+    };
+    let (mut g, mut spectrum) = (0.0, gamma[0]);
+    for (k, &gamma_k) in gamma.iter().enumerate().take(big_m + 1).skip(1) {
+        let weight = flat_top(k as f64 / big_m as f64);
+        g += 2.0 * weight * k as f64 * gamma_k;
+        spectrum += 2.0 * weight * gamma_k;
+    }
+
+    let length = |d: f64| {
+        if d <= 0.0 {
+            return 1.0;
+        }
+        ((2.0 * g * g / d).powf(1.0 / 3.0) * nf.powf(1.0 / 3.0)).clamp(1.0, b_max)
+    };
+    (
+        length(2.0 * spectrum * spectrum),
+        length(4.0 / 3.0 * spectrum * spectrum),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine;
+    use crate::innovations;
+
+    fn gaussian(n: usize, seed: u64) -> Vec<f64> {
+        let mut rng = engine::path_rng(seed, 0);
+        (0..n)
+            .map(|_| innovations::standard_normal(&mut rng))
+            .collect()
+    }
+
+    #[test]
+    fn iid_input_needs_no_blocks() {
+        let values = gaussian(20_000, 1);
+        for absolute in [false, true] {
+            let (stationary, circular) = optimal_block_length(&values, absolute);
+            assert!(
+                stationary < 2.0 && circular < 2.0,
+                "{stationary} {circular}"
+            );
+        }
+    }
+
+    #[test]
+    fn dependent_absolute_values_need_longer_blocks() {
+        // Returns whose size follows an AR(1), as with volatility
+        // clustering; their signs, and so the raw returns, are independent.
+        let noise = gaussian(20_000, 2);
+        let signs = gaussian(20_000, 3);
+        let mut size = 0.0;
+        let returns: Vec<f64> = noise
+            .iter()
+            .zip(&signs)
+            .map(|(e, s)| {
+                size = 0.9 * size + e;
+                size * s.signum()
+            })
+            .collect();
+        let (stationary, circular) = optimal_block_length(&returns, true);
+        assert!(
+            stationary > 10.0 && circular > 10.0,
+            "{stationary} {circular}"
+        );
+        let (raw, _) = optimal_block_length(&returns, false);
+        assert!(raw < 2.0, "{raw}");
+    }
+
+    /// Share of `steps` consecutive draws that continue the previous index.
+    fn continuation_share(blocks: Blocks, n: usize, steps: usize) -> f64 {
+        let mut rng = engine::path_rng(4, 0);
+        let mut cursor = Cursor::default();
+        let mut last = blocks.next_index(&mut rng, n, &mut cursor);
+        let mut continued = 0;
+        for _ in 1..steps {
+            let index = blocks.next_index(&mut rng, n, &mut cursor);
+            continued += (index == (last + 1) % n) as usize;
+            last = index;
+        }
+        continued as f64 / (steps - 1) as f64
+    }
+
+    #[test]
+    fn blocks_keep_runs_of_consecutive_shocks() {
+        let n = 100_000;
+        let iid = continuation_share(Blocks::Iid, n, 20_000);
+        assert!(iid < 0.01, "{iid}");
+        let stationary = continuation_share(Blocks::Stationary { mean_length: 10.0 }, n, 20_000);
+        assert!((stationary - 0.9).abs() < 0.01, "{stationary}");
+        let circular = continuation_share(Blocks::Circular { length: 5 }, n, 20_000);
+        assert!((circular - 0.8).abs() < 0.01, "{circular}");
+    }
+
+    #[test]
+    fn circular_blocks_wrap_to_the_start() {
+        let mut rng = engine::path_rng(5, 0);
+        let blocks = Blocks::Circular { length: 4 };
+        let mut cursor = Cursor { index: 8, left: 3 };
+        let run: Vec<usize> = (0..3)
+            .map(|_| blocks.next_index(&mut rng, 10, &mut cursor))
+            .collect();
+        assert_eq!(run, [9, 0, 1]);
+    }
+}
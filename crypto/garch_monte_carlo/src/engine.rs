@@ -0,0 +1,375 @@
+// garch_monte_carlo/src/engine.rs
+// Deterministic parallel driver shared by all simulators.
+
+use crate::stats::Tally;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::prelude::*;
+use rand_xoshiro::Xoshiro256PlusPlus;
+use rayon::prelude::*;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+/// Paths per rayon work item. Chunks are reduced in index order, so results
+/// do not depend on how rayon schedules them across threads.
+const CHUNK_SIZE: usize = 1024;
+
+/// Chunks in flight per thread. Their accumulators are merged, in order,
+/// before the next ones start, so large accumulators are never all held
+/// at once.
+const CHUNKS_PER_THREAD: usize = 4;
+
+/// Size of the first batch of an adaptive run. Later batches double the
+/// total so far.
+const MIN_BATCH: usize = 4096;
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// Independent random stream for simulation `path` under `seed`.
+pub(crate) fn path_rng(seed: u64, path: u64) -> Xoshiro256PlusPlus {
+    Xoshiro256PlusPlus::seed_from_u64(splitmix64(seed).wrapping_add(path))
+}
+
+/// Uses the caller's seed, or draws a fresh one from OS entropy.
+fn resolve_seed(seed: Option<u64>) -> u64 {
+    seed.unwrap_or_else(rand::random)
+}
+
+/// Runs `path` once for every index in `paths`, each with its own
+/// `path_rng`, folding into per-chunk accumulators that are merged in order
+/// as each window of chunks completes.
+pub(crate) fn fold_paths<A, I, F, M>(
+    paths: Range<usize>,
+    seed: u64,
+    init: I,
+    path: F,
+    merge: M,
+) -> A
+where
+    A: Send,
+    I: Fn() -> A + Sync,
+    F: Fn(&mut A, &mut Xoshiro256PlusPlus) + Sync,
+    M: Fn(A, A) -> A,
+{
+    fold_indexed(paths, seed, init, |acc, _, rng| path(acc, rng), merge)
+}
+
+/// `fold_paths` that also passes `path` the index of the path.
+pub(crate) fn fold_indexed<A, I, F, M>(
+    paths: Range<usize>,
+    seed: u64,
+    init: I,
+    path: F,
+    merge: M,
+) -> A
+where
+    A: Send,
+    I: Fn() -> A + Sync,
+    F: Fn(&mut A, usize, &mut Xoshiro256PlusPlus) + Sync,
+    M: Fn(A, A) -> A,
+{
+    let num_chunks = paths.len().div_ceil(CHUNK_SIZE);
+    let window = rayon::current_num_threads() * CHUNKS_PER_THREAD;
+    let mut acc = init();
+    for first in (0..num_chunks).step_by(window) {
+        let chunks: Vec<A> = (first..(first + window).min(num_chunks))
+            .into_par_iter()
+            .map(|chunk| {
+                let lo = paths.start + chunk * CHUNK_SIZE;
+                let hi = (lo + CHUNK_SIZE).min(paths.end);
+                let mut acc = init();
+                for i in lo..hi {
+                    let mut rng = path_rng(seed, i as u64);
+                    path(&mut acc, i, &mut rng);
+                }
+                acc
+            })
+            .collect();
+        acc = chunks.into_iter().fold(acc, &merge);
+    }
+    acc
+}
+
+/// When an adaptive run may stop before its path budget is spent.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct StopRule {
+    pub target_std_error: Option<f64>,
+    /// Start of the call and its time budget.
+    pub budget: Option<(Instant, Duration)>,
+}
+
+impl StopRule {
+    fn new(
+        target_std_error: Option<f64>,
+        max_seconds: Option<f64>,
+        started: Instant,
+    ) -> PyResult<Self> {
+        if target_std_error.is_some_and(|e| e.is_nan() || e < 0.0) {
+            return Err(PyValueError::new_err(
+                "target_std_error must not be negative",
+            ));
+        }
+        let budget = match max_seconds {
+            None => None,
+            Some(secs) => match Duration::try_from_secs_f64(secs) {
+                Ok(budget) if !budget.is_zero() => Some((started, budget)),
+                _ => {
+                    return Err(PyValueError::new_err(
+                        "max_seconds must be positive and finite",
+                    ))
+                }
+            },
+        };
+        Ok(StopRule {
+            target_std_error,
+            budget,
+        })
+    }
+
+    fn is_adaptive(&self) -> bool {
+        self.target_std_error.is_some() || self.budget.is_some()
+    }
+}
+
+/// Path budget, seed and stopping rule of one simulation call.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Run {
+    pub max_paths: usize,
+    pub seed: u64,
+    pub rule: StopRule,
+    pub started: Instant,
+}
+
+impl Run {
+    pub(crate) fn new(
+        max_paths: usize,
+        seed: Option<u64>,
+        target_std_error: Option<f64>,
+        max_seconds: Option<f64>,
+    ) -> PyResult<Self> {
+        if max_paths == 0 {
+            return Err(PyValueError::new_err("num_simulations must be positive"));
+        }
+        let started = Instant::now();
+        Ok(Run {
+            max_paths,
+            seed: resolve_seed(seed),
+            rule: StopRule::new(target_std_error, max_seconds, started)?,
+            started,
+        })
+    }
+}
+
+/// Like `fold_paths` over `0..run.max_paths`, but simulates in growing batches
+/// and stops once `std_error(acc, n)` meets the rule's target or its time
+/// budget is spent. Returns the accumulator and the number of paths simulated.
+pub(crate) fn fold_paths_until<A, I, F, M, E>(
+    run: &Run,
+    init: I,
+    path: F,
+    merge: M,
+    std_error: E,
+) -> (A, usize)
+where
+    A: Send,
+    I: Fn() -> A + Sync,
+    F: Fn(&mut A, &mut Xoshiro256PlusPlus) + Sync,
+    M: Fn(A, A) -> A,
+    E: Fn(&A, usize) -> f64,
+{
+    let Run {
+        max_paths,
+        seed,
+        rule,
+        ..
+    } = *run;
+    if !rule.is_adaptive() {
+        return (
+            fold_paths(0..max_paths, seed, &init, &path, &merge),
+            max_paths,
+        );
+    }
+
+    let mut acc = init();
+    let mut done = 0;
+    while done < max_paths {
+        let mut batch = done.max(MIN_BATCH);
+        if let Some((started, budget)) = rule.budget {
+            let elapsed = started.elapsed();
+            if elapsed >= budget {
+                break;
+            }
+            // Size the batch to what the remaining budget can afford at the
+            // throughput observed so far.
+            if done > 0 {
+                let per_path = elapsed.as_secs_f64() / done as f64;
+                let affordable = ((budget - elapsed).as_secs_f64() / per_path) as usize;
+                batch = batch.min(affordable.max(CHUNK_SIZE));
+            }
+        }
+        let hi = (done + batch).min(max_paths);
+
+        let batch_acc = fold_paths(done..hi, seed, &init, &path, &merge);
+        acc = merge(acc, batch_acc);
+        done = hi;
+
+        if rule
+            .target_std_error
+            .is_some_and(|target| std_error(&acc, done) <= target)
+        {
+            break;
+        }
+    }
+
+    (acc, done)
+}
+
+/// Number of paths for which `hit` returns true. Returns `(hits, paths)`.
+pub(crate) fn count_hits<F>(run: &Run, hit: F) -> (usize, usize)
+where
+    F: Fn(&mut Xoshiro256PlusPlus) -> bool + Sync,
+{
+    fold_paths_until(
+        run,
+        || 0usize,
+        |count, rng| *count += hit(rng) as usize,
+        |a, b| a + b,
+        |&hits, n| hit_std_error(hits, n),
+    )
+}
+
+/// Sample mean of `value` over the paths. Returns the tally and the number
+/// of paths.
+pub(crate) fn mean_until<F>(run: &Run, value: F) -> (Tally, usize)
+where
+    F: Fn(&mut Xoshiro256PlusPlus) -> f64 + Sync,
+{
+    fold_paths_until(
+        run,
+        Tally::default,
+        |tally, rng| tally.push(value(rng)),
+        Tally::merge,
+        Tally::std_error,
+    )
+}
+
+/// Standard error used by stopping rules for a hit count. Shrinks towards
+/// 1/2 so that a batch without any hits (or misses) does not report zero.
+pub(crate) fn hit_std_error(hits: usize, n: usize) -> f64 {
+    let p = (hits as f64 + 0.5) / (n as f64 + 1.0);
+    (p * (1.0 - p) / n as f64).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::innovations;
+    use crate::model::{Horizon, Model};
+    use crate::result::SimulationResult;
+    use crate::variance::VarianceModel;
+
+    fn garch_model() -> Model<'static> {
+        let mut rng = path_rng(1, 0);
+        let residuals: Vec<f64> = (0..5000)
+            .map(|_| innovations::standard_normal(&mut rng))
+            .collect();
+        let garch = VarianceModel::Garch {
+            omega: 2e-8,
+            alpha: 0.08,
+            beta: 0.9,
+        };
+        Model::filtered(garch, residuals, 1e-3, 1e-6).unwrap()
+    }
+
+    /// `run` of a 15.5-minute GARCH probability on a pool of `threads`.
+    fn simulate(threads: usize, run: &Run) -> SimulationResult {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        let model = garch_model();
+        let horizon = Horizon::from_seconds(930.0).unwrap();
+        pool.install(|| model.probability(100.0, 100.1, horizon, run))
+    }
+
+    /// The parts of a result that must match bit for bit.
+    fn bits(result: &SimulationResult) -> (u64, u64, usize, u64) {
+        (
+            result.probability.to_bits(),
+            result.std_error.to_bits(),
+            result.num_simulations,
+            result.seed,
+        )
+    }
+
+    #[test]
+    fn same_seed_gives_same_result_on_any_number_of_threads() {
+        let run = Run::new(50_000, Some(7), None, None).unwrap();
+        let single = simulate(1, &run);
+        for threads in [2, 3, 8] {
+            assert_eq!(bits(&simulate(threads, &run)), bits(&single));
+        }
+        let other = Run::new(50_000, Some(8), None, None).unwrap();
+        assert_ne!(bits(&simulate(1, &other)), bits(&single));
+    }
+
+    #[test]
+    fn adaptive_stop_is_reproducible() {
+        let run = Run::new(1_000_000, Some(7), Some(2e-3), None).unwrap();
+        let single = simulate(1, &run);
+        assert!(single.num_simulations < run.max_paths);
+        assert!(single.std_error <= 2e-3);
+        for threads in [1, 4] {
+            let again = Run::new(1_000_000, Some(7), Some(2e-3), None).unwrap();
+            assert_eq!(bits(&simulate(threads, &again)), bits(&single));
+        }
+    }
+
+    #[test]
+    fn holds_a_bounded_number_of_accumulators() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static LIVE: AtomicUsize = AtomicUsize::new(0);
+        static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+        /// Counts paths and how many of its kind exist at once.
+        struct Counter(usize);
+        impl Counter {
+            fn new() -> Self {
+                let live = LIVE.fetch_add(1, Ordering::SeqCst) + 1;
+                PEAK.fetch_max(live, Ordering::SeqCst);
+                Counter(0)
+            }
+        }
+        impl Drop for Counter {
+            fn drop(&mut self) {
+                LIVE.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+
+        let threads = 2;
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        let paths = 100 * CHUNK_SIZE;
+        let total = pool.install(|| {
+            fold_paths(
+                0..paths,
+                1,
+                Counter::new,
+                |acc, _| acc.0 += 1,
+                |mut a, b| {
+                    a.0 += b.0;
+                    a
+                },
+            )
+        });
+        assert_eq!(total.0, paths);
+        assert!(PEAK.load(Ordering::SeqCst) <= threads * CHUNKS_PER_THREAD + 1);
+    }
+}
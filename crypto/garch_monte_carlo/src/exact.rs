@@ -0,0 +1,453 @@
+// garch_monte_carlo/src/exact.rs
+// Exact bootstrap probabilities by FFT convolution of the return histogram.
+
+use crate::array::F64Array;
+use crate::engine::Run;
+use crate::model::{Horizon, Model};
+use crate::result::SimulationResult;
+use crate::stats::Moments;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::sync::{Arc, Mutex};
+
+/// Most lattice points of a terminal distribution. Horizons that would
+/// need more are put on a coarser grid.
+const MAX_POINTS: usize = 1 << 22;
+
+/// Most whole-minute distributions kept at once.
+const MAX_CACHED: usize = 64;
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn mul(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+
+    fn powi(self, mut n: usize) -> Complex {
+        let mut base = self;
+        let mut result = Complex { re: 1.0, im: 0.0 };
+        while n != 0 {
+            if n & 1 == 1 {
+                result = result.mul(base);
+            }
+            base = base.mul(base);
+            n >>= 1;
+        }
+        result
+    }
+}
+
+/// In-place radix-2 FFT of a power-of-two length, unnormalized either way.
+/// Twiddles are computed directly rather than by repeated multiplication,
+/// so rounding does not build up over long transforms.
+fn fft(data: &mut [Complex], inverse: bool) {
+    let n = data.len();
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let twiddles: Vec<Complex> = (0..n / 2)
+        .map(|k| {
+            let angle = sign * 2.0 * PI * k as f64 / n as f64;
+            Complex {
+                re: angle.cos(),
+                im: angle.sin(),
+            }
+        })
+        .collect();
+    let mut len = 2;
+    while len <= n {
+        let stride = n / len;
+        for block in data.chunks_exact_mut(len) {
+            let (lower, upper) = block.split_at_mut(len / 2);
+            for (k, (u, v)) in lower.iter_mut().zip(upper).enumerate() {
+                let t = v.mul(twiddles[k * stride]);
+                *v = Complex {
+                    re: u.re - t.re,
+                    im: u.im - t.im,
+                };
+                *u = Complex {
+                    re: u.re + t.re,
+                    im: u.im + t.im,
+                };
+            }
+        }
+        len <<= 1;
+    }
+}
+
+/// Mass of `scale` times each of `values` on the lattice `offset + k step`,
+/// each value split between its two neighbouring points so the mean is
+/// kept. Returns `(offset, masses)`.
+fn histogram(values: &[f64], scale: f64, step: f64) -> (f64, Vec<f64>) {
+    let (lo, hi) = values
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| {
+            (lo.min(v), hi.max(v))
+        });
+    let (lo, hi) = (scale * lo, scale * hi);
+    let mut masses = vec![0.0; ((hi - lo) / step) as usize + 2];
+    let weight = 1.0 / values.len() as f64;
+    for &v in values {
+        let position = (scale * v - lo) / step;
+        let k = position as usize;
+        let frac = position - k as f64;
+        masses[k] += weight * (1.0 - frac);
+        masses[k + 1] += weight * frac;
+    }
+    (lo, masses)
+}
+
+/// Lattice points the sum of `minutes` returns needs at `step`.
+fn support(range: f64, minutes: usize, step: f64) -> usize {
+    minutes * ((range / step) as usize + 2) + 1
+}
+
+/// Distribution of the log-return over whole minutes, on the lattice
+/// `offset + k step`.
+struct Lattice {
+    offset: f64,
+    step: f64,
+    /// `cdf[k]`: probability of ending at or below point `k`.
+    cdf: Vec<f64>,
+}
+
+impl Lattice {
+    /// P(log-return > `x`), spreading each point's mass evenly over the
+    /// step around it so the answer moves smoothly with `x`.
+    fn survival(&self, x: f64) -> f64 {
+        let t = (x - self.offset) / self.step + 0.5;
+        if t <= 0.0 {
+            return 1.0;
+        }
+        let k = t.floor();
+        if k >= self.cdf.len() as f64 {
+            return 0.0;
+        }
+        let k = k as usize;
+        let below = if k == 0 { 0.0 } else { self.cdf[k - 1] };
+        let frac = t - k as f64;
+        (1.0 - (below + frac * (self.cdf[k] - below))).clamp(0.0, 1.0)
+    }
+}
+
+/// The distribution `calculate_probability_plain` samples, computed
+/// instead of simulated: its minutes are i.i.d. draws from the returns, so
+/// the horizon's log-return is their histogram convolved with itself once
+/// per minute. A partial minute scales the returns by the square root of
+/// its length, so its draw is averaged over them at query time. The
+/// convolution is an FFT power, cached per number of whole minutes (the
+/// `MAX_CACHED` most recently used), after which a probability is a
+/// lookup per return.
+#[pyclass]
+pub struct ExactBootstrap {
+    returns: Vec<f64>,
+    /// Grid spacing for one whole minute before any coarsening.
+    step: f64,
+    range: f64,
+    cache: Mutex<Cache>,
+}
+
+/// Whole-minute distributions by their number of minutes, each with the
+/// tick it was last used at.
+#[derive(Default)]
+struct Cache {
+    lattices: HashMap<usize, (Arc<Lattice>, u64)>,
+    tick: u64,
+}
+
+impl Cache {
+    fn get(&mut self, whole: usize) -> Option<Arc<Lattice>> {
+        self.tick += 1;
+        let (lattice, used) = self.lattices.get_mut(&whole)?;
+        *used = self.tick;
+        Some(lattice.clone())
+    }
+
+    /// Inserts `lattice`, evicting the least recently used one when full.
+    fn insert(&mut self, whole: usize, lattice: Arc<Lattice>) {
+        if self.lattices.len() >= MAX_CACHED && !self.lattices.contains_key(&whole) {
+            let oldest = self.lattices.iter().min_by_key(|(_, (_, used))| *used);
+            if let Some((&oldest, _)) = oldest {
+                self.lattices.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.lattices.insert(whole, (lattice, self.tick));
+    }
+}
+
+impl ExactBootstrap {
+    /// Distribution of the sum of `whole` returns, from the cache if it
+    /// is there.
+    fn lattice(&self, whole: usize) -> Arc<Lattice> {
+        if let Some(lattice) = self.cache.lock().unwrap().get(whole) {
+            return lattice;
+        }
+        let lattice = Arc::new(self.convolve(whole));
+        self.cache.lock().unwrap().insert(whole, lattice.clone());
+        lattice
+    }
+
+    /// P(log-return over `horizon` > `x`): the whole minutes' distribution
+    /// averaged over the head's draw of each return.
+    pub(crate) fn survival(&self, horizon: Horizon, x: f64) -> f64 {
+        let lattice = self.lattice(horizon.whole);
+        if horizon.head == 0.0 {
+            return lattice.survival(x);
+        }
+        let scale = horizon.head.sqrt();
+        let total: f64 = self
+            .returns
+            .iter()
+            .map(|r| lattice.survival(x - scale * r))
+            .sum();
+        total / self.returns.len() as f64
+    }
+
+    fn convolve(&self, whole: usize) -> Lattice {
+        let mut step = self.step;
+        let needed = support(self.range, whole, step);
+        if needed > MAX_POINTS {
+            step *= needed as f64 / MAX_POINTS as f64;
+        }
+        let points = support(self.range, whole, step);
+        let size = points.next_power_of_two();
+        let (one_offset, masses) = histogram(&self.returns, 1.0, step);
+        let mut spectrum = vec![Complex::default(); size];
+        for (z, m) in spectrum.iter_mut().zip(masses) {
+            z.re = m;
+        }
+        fft(&mut spectrum, false);
+        spectrum.par_iter_mut().for_each(|z| *z = z.powi(whole));
+        let offset = whole as f64 * one_offset;
+        fft(&mut spectrum, true);
+
+        let mut cdf = Vec::with_capacity(points);
+        let mut total = 0.0;
+        for z in &spectrum[..points] {
+            total += (z.re / size as f64).max(0.0);
+            cdf.push(total);
+        }
+        cdf.iter_mut().for_each(|c| *c /= total);
+        Lattice { offset, step, cdf }
+    }
+}
+
+#[pymethods]
+impl ExactBootstrap {
+    /// From 1-minute log returns, on a grid of `resolution` points per
+    /// standard deviation of one return.
+    #[staticmethod]
+    #[pyo3(signature = (returns, resolution=64.0))]
+    pub(crate) fn from_returns(returns: F64Array, resolution: f64) -> PyResult<Self> {
+        let returns = returns.into_vec();
+        if returns.is_empty() || returns.iter().any(|r| !r.is_finite()) {
+            return Err(PyValueError::new_err("need at least one finite return"));
+        }
+        if resolution.is_nan() || resolution <= 0.0 {
+            return Err(PyValueError::new_err("resolution must be positive"));
+        }
+        let (lo, hi) = returns
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &r| {
+                (lo.min(r), hi.max(r))
+            });
+        let sd = Moments::of(&returns).variance.sqrt();
+        Ok(ExactBootstrap {
+            returns,
+            step: sd.max(f64::MIN_POSITIVE) / resolution,
+            range: hi - lo,
+            cache: Mutex::default(),
+        })
+    }
+
+    /// P(price > target_price) after `horizon_seconds`, with no Monte Carlo
+    /// noise. The first call for a number of whole minutes runs the
+    /// convolution; later ones read the cached distribution, whatever the
+    /// partial minute.
+    fn probability(
+        &self,
+        py: Python<'_>,
+        current_price: f64,
+        target_price: f64,
+        horizon_seconds: f64,
+    ) -> PyResult<f64> {
+        let threshold = (target_price / current_price).ln();
+        let horizon = Horizon::from_seconds(horizon_seconds)?;
+        if horizon.steps().next().is_none() {
+            return Ok((threshold < 0.0) as u8 as f64);
+        }
+        Ok(py.allow_threads(|| self.survival(horizon, threshold)))
+    }
+
+    /// `probability` next to `calculate_probability_plain`'s estimate for
+    /// the same returns and query, as `(exact, simulated)`; the exact value
+    /// should fall inside the simulation's interval about 95% of the time.
+    #[pyo3(signature = (
+        current_price, target_price, horizon_seconds, num_simulations, seed=None
+    ))]
+    fn cross_check(
+        &self,
+        py: Python<'_>,
+        current_price: f64,
+        target_price: f64,
+        horizon_seconds: f64,
+        num_simulations: usize,
+        seed: Option<u64>,
+    ) -> PyResult<(f64, SimulationResult)> {
+        let exact = self.probability(py, current_price, target_price, horizon_seconds)?;
+        let run = Run::new(num_simulations, seed, None, None)?;
+        let horizon = Horizon::from_seconds(horizon_seconds)?;
+        let model = Model::bootstrap(self.returns.as_slice())?;
+        let simulated =
+            py.allow_threads(|| model.probability(current_price, target_price, horizon, &run));
+        Ok((exact, simulated))
+    }
+
+    /// Lattice spacing of a one-minute return, in log-return.
+    #[getter]
+    fn grid_step(&self) -> f64 {
+        self.step
+    }
+
+    /// Drops the cached distributions.
+    fn clear_cache(&self) {
+        self.cache.lock().unwrap().lattices.clear();
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ExactBootstrap(num_returns={}, grid_step={:.3e}, cached_horizons={})",
+            self.returns.len(),
+            self.step,
+            self.cache.lock().unwrap().lattices.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine;
+    use crate::innovations;
+
+    fn exact(returns: Vec<f64>) -> ExactBootstrap {
+        ExactBootstrap::from_returns(F64Array::Owned(returns), 64.0).unwrap()
+    }
+
+    /// P(K > k) and P(K = k) for K ~ Binomial(n, 1/2).
+    fn binomial_tail(n: usize, k: usize) -> (f64, f64) {
+        let pmf = |j: usize| {
+            let ln_choose = innovations::ln_gamma(n as f64 + 1.0)
+                - innovations::ln_gamma(j as f64 + 1.0)
+                - innovations::ln_gamma((n - j) as f64 + 1.0);
+            (ln_choose - n as f64 * 2f64.ln()).exp()
+        };
+        ((k + 1..=n).map(pmf).sum(), pmf(k))
+    }
+
+    #[test]
+    fn two_point_returns_give_the_binomial() {
+        // With returns of +-d, n minutes end at d (2K - n).
+        let d = 1e-3;
+        let exact = exact(vec![-d, d]);
+        let n = 12;
+        let lattice = exact.convolve(n);
+        for k in 0..n {
+            // Halfway between the atoms at 2k - n and 2k + 2 - n.
+            let x = d * (2 * k + 1) as f64 - d * n as f64;
+            let (above, _) = binomial_tail(n, k);
+            assert!((lattice.survival(x) - above).abs() < 1e-9, "k {k}");
+        }
+        assert_eq!(lattice.survival(-2.0 * d * n as f64), 1.0);
+        assert_eq!(lattice.survival(2.0 * d * n as f64), 0.0);
+    }
+
+    #[test]
+    fn partial_minute_is_scaled_by_its_square_root() {
+        // Half a minute adds +-d sqrt(1/2): above d (2k - n) means K > k, or
+        // K = k with the half minute up.
+        let d = 1e-3;
+        let exact = exact(vec![-d, d]);
+        let n = 9;
+        let horizon = Horizon::from_seconds(60.0 * n as f64 + 30.0).unwrap();
+        for k in 0..=n {
+            let x = d * (2 * k) as f64 - d * n as f64;
+            let (above, at) = binomial_tail(n, k);
+            assert!(
+                (exact.survival(horizon, x) - (above + 0.5 * at)).abs() < 1e-9,
+                "k {k}"
+            );
+        }
+    }
+
+    #[test]
+    fn agrees_with_the_simulated_bootstrap() {
+        let mut rng = engine::path_rng(5, 0);
+        let returns: Vec<f64> = (0..2000)
+            .map(|_| 1e-3 * innovations::standard_normal(&mut rng).powi(3))
+            .collect();
+        let exact = exact(returns.clone());
+        let model = Model::bootstrap(returns).unwrap();
+        let horizon = Horizon::from_seconds(930.0).unwrap();
+        let run = Run::new(400_000, Some(3), None, None).unwrap();
+        for target in [99.0, 99.8, 100.0, 100.3, 101.0] {
+            let simulated = model.probability(100.0, target, horizon, &run);
+            let p = exact.survival(horizon, (target / 100.0f64).ln());
+            let gap = (p - simulated.probability).abs();
+            assert!(gap < 4.0 * simulated.std_error + 1e-4, "target {target}");
+        }
+    }
+
+    #[test]
+    fn partial_minutes_share_the_whole_minutes_distribution() {
+        let mut rng = engine::path_rng(6, 0);
+        let returns: Vec<f64> = (0..500)
+            .map(|_| 1e-3 * innovations::standard_normal(&mut rng))
+            .collect();
+        let exact = exact(returns);
+        let lattice = exact.lattice(15);
+        let mut last = 1.0;
+        for seconds in [930.0, 930.25, 937.5, 959.9] {
+            let horizon = Horizon::from_seconds(seconds).unwrap();
+            // A longer head spreads the distribution, so fewer paths stay
+            // above a target below the start.
+            let p = exact.survival(horizon, -2e-3);
+            assert!(p < last, "{seconds} s");
+            last = p;
+        }
+        assert_eq!(exact.cache.lock().unwrap().lattices.len(), 1);
+        assert!(Arc::ptr_eq(&lattice, &exact.lattice(15)));
+    }
+
+    #[test]
+    fn cache_evicts_the_least_recently_used() {
+        let exact = exact(vec![-1e-3, 1e-3]);
+        let first = exact.lattice(1);
+        for whole in 2..=MAX_CACHED {
+            exact.lattice(whole);
+        }
+        assert!(Arc::ptr_eq(&first, &exact.lattice(1)));
+        exact.lattice(MAX_CACHED + 1);
+        let cache = exact.cache.lock().unwrap();
+        assert_eq!(cache.lattices.len(), MAX_CACHED);
+        assert!(cache.lattices.contains_key(&1));
+        assert!(!cache.lattices.contains_key(&2));
+    }
+}
@@ -0,0 +1,328 @@
+// garch_monte_carlo/src/fit.rs
+// Gaussian quasi-maximum likelihood fits of the variance recursions.
+
+use crate::array;
+use crate::model::Model;
+use crate::optimize;
+use crate::simulator::Simulator;
+use crate::variance::{VarianceKind, VarianceModel};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::f64::consts::PI;
+
+/// Relative tolerance on the mean negative log-likelihood.
+const TOLERANCE: f64 = 1e-9;
+
+/// Parameter estimates and filtered series of a GARCH-family fit, on the
+/// scale of the returns passed in (no rescaling by 100).
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct GarchFit {
+    variance: VarianceModel,
+    /// Gaussian log-likelihood at the estimate.
+    #[pyo3(get)]
+    pub log_likelihood: f64,
+    /// Whether the simplex met its tolerance within `max_iterations`.
+    #[pyo3(get)]
+    pub converged: bool,
+    #[pyo3(get)]
+    pub iterations: usize,
+    /// Last return, i.e. the residual entering the next variance.
+    #[pyo3(get)]
+    pub last_resid: f64,
+    /// Conditional variance of the last return.
+    #[pyo3(get)]
+    pub last_sigma_sq: f64,
+    std_residuals: Vec<f64>,
+    conditional_variances: Vec<f64>,
+}
+
+#[pymethods]
+impl GarchFit {
+    /// `"garch"`, `"gjr"` or `"egarch"`.
+    #[getter]
+    fn model(&self) -> &'static str {
+        self.variance.kind().name()
+    }
+
+    #[getter]
+    fn omega(&self) -> f64 {
+        self.variance.params().0
+    }
+
+    #[getter]
+    fn alpha(&self) -> f64 {
+        self.variance.params().1
+    }
+
+    /// Leverage coefficient, or `None` for symmetric GARCH.
+    #[getter]
+    fn gamma(&self) -> Option<f64> {
+        self.variance.params().2
+    }
+
+    #[getter]
+    fn beta(&self) -> f64 {
+        self.variance.params().3
+    }
+
+    /// `alpha + gamma/2 + beta` (`beta` for EGARCH); values near one mean
+    /// shocks to variance barely decay.
+    #[getter]
+    fn persistence(&self) -> f64 {
+        self.variance.persistence()
+    }
+
+    /// Returns divided by their conditional volatility, as a NumPy array.
+    #[getter]
+    fn std_residuals(&self, py: Python<'_>) -> PyResult<PyObject> {
+        array::to_numpy(py, self.std_residuals.clone())
+    }
+
+    /// Conditional variance of each return, as a NumPy array.
+    #[getter]
+    fn conditional_variances(&self, py: Python<'_>) -> PyResult<PyObject> {
+        array::to_numpy(py, self.conditional_variances.clone())
+    }
+
+    /// A `Simulator` resampling this fit's standardized residuals under its
+    /// variance recursion, starting from the variance after the last return.
+    #[pyo3(signature = (window=None))]
+    fn simulator(&self, window: Option<usize>) -> PyResult<Simulator> {
+        let mut model = Model::filtered(
+            self.variance,
+            self.std_residuals.clone(),
+            self.last_resid,
+            self.last_sigma_sq,
+        )?;
+        model.window = window;
+        Ok(Simulator::new(model))
+    }
+
+    fn __repr__(&self) -> String {
+        let (omega, alpha, gamma, beta) = self.variance.params();
+        let gamma = gamma.map_or(String::new(), |g| format!(", gamma={g:.6}"));
+        format!(
+            "GarchFit(model={:?}, omega={:.6e}, alpha={:.6}{}, beta={:.6}, log_likelihood={:.3}, converged={}, iterations={})",
+            self.model(), omega, alpha, gamma, beta, self.log_likelihood, self.converged, self.iterations
+        )
+    }
+}
+
+/// Conditional variances of `returns` under `variance`, starting from
+/// `backcast` for the first one.
+fn filter(variance: &VarianceModel, returns: &[f64], backcast: f64) -> Vec<f64> {
+    let mut sigma_sq = backcast;
+    let mut r_prev = None;
+    returns
+        .iter()
+        .map(|&r| {
+            if let Some(r_prev) = r_prev {
+                sigma_sq = variance.next_variance(sigma_sq, r_prev);
+            }
+            r_prev = Some(r);
+            sigma_sq
+        })
+        .collect()
+}
+
+/// Mean Gaussian negative log-likelihood of `returns` with variances
+/// `variances`.
+fn mean_nll(returns: &[f64], variances: &[f64]) -> f64 {
+    let sum: f64 = returns
+        .iter()
+        .zip(variances)
+        .map(|(r, v)| v.ln() + r * r / v)
+        .sum();
+    0.5 * ((2.0 * PI).ln() + sum / returns.len() as f64)
+}
+
+fn logistic(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+fn logit(p: f64) -> f64 {
+    (p / (1.0 - p)).ln()
+}
+
+/// Starting point in the unconstrained parameters of `from_unconstrained`:
+/// alpha 0.05 and beta 0.90 for GARCH, alpha 0.03, gamma 0.06 and beta 0.90
+/// for GJR, and beta 0.98 with a mild leverage effect for EGARCH.
+fn start(kind: VarianceKind) -> Vec<f64> {
+    match kind {
+        VarianceKind::Garch => vec![0.05f64.ln(), logit(0.95), (0.05f64 / 0.90).ln()],
+        VarianceKind::GjrGarch => vec![
+            0.04f64.ln(),
+            logit(0.96),
+            (0.03f64 / 0.90).ln(),
+            (0.03f64 / 0.90).ln(),
+        ],
+        VarianceKind::Egarch => vec![0.0, 0.1, -0.05, 0.98f64.atanh()],
+    }
+}
+
+/// Maps unconstrained parameters onto a valid model. For the GARCH family:
+/// log of omega relative to the mean squared return `backcast`, logit of
+/// the persistence, and log-weights of alpha (and gamma/2) relative to beta
+/// in splitting it, so every point is positive and, short of the logistic
+/// rounding to one, covariance-stationary.
+/// For EGARCH: the offset of omega from the value that makes `backcast` the
+/// long-run variance, alpha and gamma as they are, and atanh of beta.
+fn from_unconstrained(kind: VarianceKind, theta: &[f64], backcast: f64) -> VarianceModel {
+    match kind {
+        VarianceKind::Garch | VarianceKind::GjrGarch => {
+            let omega = backcast * theta[0].exp();
+            let persistence = logistic(theta[1]);
+            let weights: Vec<f64> = theta[2..].iter().map(|t| t.exp()).collect();
+            let total = 1.0 + weights.iter().sum::<f64>();
+            let alpha = persistence * weights[0] / total;
+            let beta = persistence / total;
+            match weights.get(1) {
+                None => VarianceModel::Garch { omega, alpha, beta },
+                Some(w) => VarianceModel::GjrGarch {
+                    omega,
+                    alpha,
+                    gamma: 2.0 * persistence * w / total,
+                    beta,
+                },
+            }
+        }
+        VarianceKind::Egarch => {
+            let beta = theta[3].tanh();
+            VarianceModel::Egarch {
+                omega: (1.0 - beta) * backcast.ln() + theta[0],
+                alpha: theta[1],
+                gamma: theta[2],
+                beta,
+            }
+        }
+    }
+}
+
+/// Fits a zero-mean `kind` recursion to `returns` by Gaussian QMLE,
+/// backcasting the first variance with the mean squared return. The simplex
+/// is restarted once from its first optimum, which guards against early
+/// collapse.
+pub(crate) fn fit(
+    kind: VarianceKind,
+    returns: &[f64],
+    max_iterations: usize,
+) -> PyResult<GarchFit> {
+    if returns.len() < 2 || returns.iter().any(|r| !r.is_finite()) {
+        return Err(PyValueError::new_err(
+            "need at least two finite returns to fit",
+        ));
+    }
+    let backcast = returns.iter().map(|r| r * r).sum::<f64>() / returns.len() as f64;
+    if backcast <= 0.0 {
+        return Err(PyValueError::new_err("returns are all zero"));
+    }
+
+    let objective = |theta: &[f64]| {
+        let variance = from_unconstrained(kind, theta, backcast);
+        // The logistic rounds to exactly one far enough out.
+        if variance.persistence() >= 1.0 {
+            return f64::NAN;
+        }
+        mean_nll(returns, &filter(&variance, returns, backcast))
+    };
+    let first = optimize::nelder_mead(objective, &start(kind), 0.5, TOLERANCE, max_iterations);
+    let second = optimize::nelder_mead(
+        objective,
+        &first.x,
+        0.1,
+        TOLERANCE,
+        max_iterations.saturating_sub(first.iterations),
+    );
+
+    let variance = from_unconstrained(kind, &second.x, backcast);
+    let conditional_variances = filter(&variance, returns, backcast);
+    let std_residuals = returns
+        .iter()
+        .zip(&conditional_variances)
+        .map(|(r, v)| r / v.sqrt())
+        .collect();
+    Ok(GarchFit {
+        variance,
+        log_likelihood: -second.value * returns.len() as f64,
+        converged: second.converged,
+        iterations: first.iterations + second.iterations,
+        last_resid: returns[returns.len() - 1],
+        last_sigma_sq: conditional_variances[returns.len() - 1],
+        std_residuals,
+        conditional_variances,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine;
+    use crate::innovations;
+
+    /// `n` returns of a GARCH(1,1) with Gaussian shocks, started at the
+    /// unconditional variance (or `omega` when there is none).
+    fn simulate(omega: f64, alpha: f64, beta: f64, n: usize, seed: u64) -> Vec<f64> {
+        let garch = VarianceModel::Garch { omega, alpha, beta };
+        let mut rng = engine::path_rng(seed, 0);
+        let mut sigma_sq = if alpha + beta < 1.0 {
+            omega / (1.0 - alpha - beta)
+        } else {
+            omega
+        };
+        (0..n)
+            .map(|_| {
+                let r = sigma_sq.sqrt() * innovations::standard_normal(&mut rng);
+                sigma_sq = garch.next_variance(sigma_sq, r);
+                r
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recovers_garch_parameters() {
+        let returns = simulate(2e-8, 0.08, 0.9, 50_000, 11);
+        let fit = fit(VarianceKind::Garch, &returns, 2000).unwrap();
+        let (omega, alpha, gamma, beta) = fit.variance.params();
+        assert!(fit.converged);
+        assert_eq!(gamma, None);
+        assert!((alpha - 0.08).abs() < 0.015, "alpha {alpha}");
+        assert!((beta - 0.9).abs() < 0.02, "beta {beta}");
+        assert!((fit.persistence() - 0.98).abs() < 0.01);
+        // The unconditional variance is pinned down better than omega.
+        let long_run = omega / (1.0 - alpha - beta);
+        assert!((long_run / 1e-6 - 1.0).abs() < 0.15, "long-run {long_run}");
+        assert_eq!(fit.std_residuals.len(), returns.len());
+        assert_eq!(fit.last_resid, returns[returns.len() - 1]);
+    }
+
+    #[test]
+    fn explosive_returns_still_fit_a_stationary_model() {
+        let returns = simulate(2e-8, 0.15, 0.9, 2000, 12);
+        for kind in [VarianceKind::Garch, VarianceKind::GjrGarch] {
+            let fit = fit(kind, &returns, 2000).unwrap();
+            let persistence = fit.persistence();
+            assert!(persistence > 0.9 && persistence < 1.0, "{persistence}");
+            assert!(fit.log_likelihood.is_finite());
+        }
+    }
+
+    #[test]
+    fn rejects_degenerate_returns() {
+        for returns in [
+            vec![],
+            vec![1e-3],
+            vec![0.0; 100],
+            vec![1e-3, f64::NAN, -1e-3],
+            vec![1e-3, f64::INFINITY, -1e-3],
+        ] {
+            for kind in [
+                VarianceKind::Garch,
+                VarianceKind::GjrGarch,
+                VarianceKind::Egarch,
+            ] {
+                assert!(fit(kind, &returns, 100).is_err(), "{returns:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,355 @@
+// garch_monte_carlo/src/har.rs
+// HAR-RV: realized-variance forecasts from 5-minute, hourly and daily components.
+
+use crate::array;
+use crate::model::{Horizon, Model};
+use crate::simulator::Simulator;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// Minutes averaged by the 5-minute, hourly and daily components.
+const COMPONENTS: [usize; 3] = [5, 60, 1440];
+/// Minutes the regression forecasts ahead, and the step the forecast is
+/// iterated in.
+const TARGET: usize = 5;
+/// Minutes of variance forecast kept; later minutes reuse the last one.
+const FORECAST_MINUTES: usize = 1440;
+
+/// Heterogeneous autoregressive model of realized variance (Corsi 2009):
+/// the mean squared 1-minute return over the next 5 minutes is linear in
+/// its means over the last 5, 60 and 1440 minutes.
+#[derive(Clone, Debug)]
+pub(crate) struct Har {
+    /// Intercept, then the 5-minute, hourly and daily loadings.
+    pub coefficients: [f64; 4],
+    /// Squared returns of the last day, oldest first.
+    recent: Vec<f64>,
+    /// Forecast variance of each coming minute, the first being the one in
+    /// progress or about to start.
+    forecast: Vec<f64>,
+}
+
+impl Har {
+    /// `recent` are at least a day of 1-minute returns, the last one just
+    /// closed.
+    pub(crate) fn new(coefficients: [f64; 4], recent: &[f64]) -> PyResult<Self> {
+        let day = COMPONENTS[2];
+        if recent.len() < day || recent.iter().any(|r| !r.is_finite()) {
+            return Err(PyValueError::new_err(
+                "need at least a day (1440) of finite 1-minute returns",
+            ));
+        }
+        if coefficients.iter().any(|c| !c.is_finite()) {
+            return Err(PyValueError::new_err("HAR coefficients must be finite"));
+        }
+        let recent = recent[recent.len() - day..].iter().map(|r| r * r).collect();
+        let mut har = Har {
+            coefficients,
+            recent,
+            forecast: Vec::new(),
+        };
+        har.refresh();
+        Ok(har)
+    }
+
+    /// Regression prediction from the component means at the end of the
+    /// squared returns whose running sums are `prefix`, floored just above
+    /// zero.
+    fn predict(&self, prefix: &[f64]) -> f64 {
+        let end = prefix.len() - 1;
+        let [c, short, hour, day] = self.coefficients;
+        let mean = |w: usize| (prefix[end] - prefix[end - w]) / w as f64;
+        let v = c
+            + short * mean(COMPONENTS[0])
+            + hour * mean(COMPONENTS[1])
+            + day * mean(COMPONENTS[2]);
+        v.max(1e-6 * mean(COMPONENTS[2])).max(f64::MIN_POSITIVE)
+    }
+
+    /// Iterates the 5-minute forecast over `FORECAST_MINUTES`, feeding each
+    /// prediction back in as the realized variance of its minutes.
+    fn refresh(&mut self) {
+        let mut prefix = Vec::with_capacity(self.recent.len() + FORECAST_MINUTES + 1);
+        prefix.push(0.0);
+        for v in &self.recent {
+            prefix.push(prefix[prefix.len() - 1] + v);
+        }
+        let mut forecast = Vec::with_capacity(FORECAST_MINUTES);
+        while forecast.len() < FORECAST_MINUTES {
+            let v = self.predict(&prefix);
+            for _ in 0..TARGET {
+                forecast.push(v);
+                prefix.push(prefix[prefix.len() - 1] + v);
+            }
+        }
+        forecast.truncate(FORECAST_MINUTES);
+        self.forecast = forecast;
+    }
+
+    /// Forecast variance of the `minute`-th coming minute.
+    pub(crate) fn variance(&self, minute: usize) -> f64 {
+        self.forecast[minute.min(FORECAST_MINUTES - 1)]
+    }
+
+    /// Forecast variance of the log return over `horizon`.
+    pub(crate) fn term_variance(&self, horizon: Horizon) -> f64 {
+        horizon
+            .steps()
+            .enumerate()
+            .map(|(k, dt)| dt * self.variance(k))
+            .sum()
+    }
+
+    /// Rolls the components forward by one observed 1-minute log return.
+    pub(crate) fn push(&mut self, r: f64) {
+        self.recent.push(r * r);
+        self.recent.drain(..1);
+        self.refresh();
+    }
+}
+
+/// HAR regression fit, plus the returns standardized by its one-step
+/// forecasts for the simulator to resample.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct HarFit {
+    har: Har,
+    /// Share of the 5-minute realized variance's variation explained.
+    #[pyo3(get)]
+    pub r_squared: f64,
+    /// Minutes regressed on.
+    #[pyo3(get)]
+    pub num_observations: usize,
+    std_residuals: Vec<f64>,
+}
+
+#[pymethods]
+impl HarFit {
+    /// `[intercept, five_minute, hourly, daily]`, the intercept in
+    /// variance of a 1-minute return.
+    #[getter]
+    fn coefficients(&self) -> Vec<f64> {
+        self.har.coefficients.to_vec()
+    }
+
+    /// Forecast variance of each of the next `minutes` 1-minute returns.
+    #[pyo3(signature = (minutes=60))]
+    fn variances(&self, py: Python<'_>, minutes: usize) -> PyResult<PyObject> {
+        array::to_numpy(py, (0..minutes).map(|k| self.har.variance(k)).collect())
+    }
+
+    /// Forecast variance of the log return over the next
+    /// `horizon_seconds`.
+    fn forecast(&self, horizon_seconds: f64) -> PyResult<f64> {
+        Ok(self
+            .har
+            .term_variance(Horizon::from_seconds(horizon_seconds)?))
+    }
+
+    /// Returns over the square root of their one-step forecast variance,
+    /// rescaled to unit variance, as a NumPy array.
+    #[getter]
+    fn std_residuals(&self, py: Python<'_>) -> PyResult<PyObject> {
+        array::to_numpy(py, self.std_residuals.clone())
+    }
+
+    /// A HAR `Simulator` continuing from the end of the fitted returns.
+    #[pyo3(signature = (window=None))]
+    fn simulator(&self, window: Option<usize>) -> PyResult<Simulator> {
+        let mut model = Model::har(self.har.clone(), self.std_residuals.clone())?;
+        model.window = window;
+        Ok(Simulator::new(model))
+    }
+
+    fn __repr__(&self) -> String {
+        let [c, short, hour, day] = self.har.coefficients;
+        format!(
+            "HarFit(intercept={:.6e}, five_minute={:.4}, hourly={:.4}, daily={:.4}, r_squared={:.4}, num_observations={})",
+            c, short, hour, day, self.r_squared, self.num_observations
+        )
+    }
+}
+
+/// Ordinary least squares of the mean squared return over each next 5
+/// minutes on its means over the last 5, 60 and 1440, at every minute with
+/// a full day behind it. Variances are divided by their overall mean while
+/// solving so the normal equations stay well conditioned.
+pub(crate) fn fit_har(returns: &[f64]) -> PyResult<HarFit> {
+    let day = COMPONENTS[2];
+    let n = returns.len();
+    if n < day + 10 * TARGET || returns.iter().any(|r| !r.is_finite()) {
+        return Err(PyValueError::new_err(
+            "need more than a day (1440) of finite 1-minute returns",
+        ));
+    }
+    let scale = returns.iter().map(|r| r * r).sum::<f64>() / n as f64;
+    if scale <= 0.0 {
+        return Err(PyValueError::new_err("returns are all zero"));
+    }
+    let mut prefix = vec![0.0; n + 1];
+    for (t, r) in returns.iter().enumerate() {
+        prefix[t + 1] = prefix[t] + r * r / scale;
+    }
+    // Means over [t - w, t) and, for the target, [t, t + TARGET).
+    let mean = |t: usize, w: usize| (prefix[t] - prefix[t - w]) / w as f64;
+    let regressors = |t: usize| {
+        [
+            1.0,
+            mean(t, COMPONENTS[0]),
+            mean(t, COMPONENTS[1]),
+            mean(t, COMPONENTS[2]),
+        ]
+    };
+
+    let mut xtx = [[0.0; 4]; 4];
+    let mut xty = [0.0; 4];
+    let (mut sum_y, mut sum_yy) = (0.0, 0.0);
+    let observations = day..=n - TARGET;
+    for t in observations.clone() {
+        let x = regressors(t);
+        let y = mean(t + TARGET, TARGET);
+        for i in 0..4 {
+            for j in 0..4 {
+                xtx[i][j] += x[i] * x[j];
+            }
+            xty[i] += x[i] * y;
+        }
+        sum_y += y;
+        sum_yy += y * y;
+    }
+    let beta =
+        solve(xtx, xty).ok_or_else(|| PyValueError::new_err("HAR regressors are collinear"))?;
+    let num_observations = observations.clone().count();
+    let mut sse = 0.0;
+    for t in observations {
+        let x = regressors(t);
+        let fitted: f64 = x.iter().zip(&beta).map(|(x, b)| x * b).sum();
+        sse += (mean(t + TARGET, TARGET) - fitted).powi(2);
+    }
+    let nf = num_observations as f64;
+    let sst = sum_yy - sum_y * sum_y / nf;
+    let r_squared = if sst > 0.0 { 1.0 - sse / sst } else { 0.0 };
+
+    let coefficients = [beta[0] * scale, beta[1], beta[2], beta[3]];
+    let har = Har::new(coefficients, returns)?;
+    let mut std_residuals: Vec<f64> = (day..n)
+        .map(|t| {
+            let x = regressors(t);
+            let fitted: f64 = x.iter().zip(&beta).map(|(x, b)| x * b).sum();
+            returns[t] / (fitted.max(1e-6) * scale).sqrt()
+        })
+        .collect();
+    let rms =
+        (std_residuals.iter().map(|z| z * z).sum::<f64>() / std_residuals.len() as f64).sqrt();
+    std_residuals.iter_mut().for_each(|z| *z /= rms);
+
+    Ok(HarFit {
+        har,
+        r_squared,
+        num_observations,
+        std_residuals,
+    })
+}
+
+/// Solves `a x = b` by Gaussian elimination with partial pivoting, or
+/// `None` if `a` is singular to working precision: a pivot is rounding
+/// noise when it is tiny next to the largest entry of `a`.
+fn solve(mut a: [[f64; 4]; 4], mut b: [f64; 4]) -> Option<[f64; 4]> {
+    let largest = a.iter().flatten().fold(0.0, |m: f64, x| m.max(x.abs()));
+    for col in 0..4 {
+        let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() <= 1e-10 * largest {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let (top, bottom) = a.split_at_mut(col + 1);
+        let pivot_row = &top[col];
+        for (offset, row) in bottom.iter_mut().enumerate() {
+            let factor = row[col] / pivot_row[col];
+            for (x, p) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                *x -= factor * p;
+            }
+            b[col + 1 + offset] -= factor * b[col];
+        }
+    }
+    let mut x = [0.0; 4];
+    for row in (0..4).rev() {
+        let tail: f64 = (row + 1..4).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - tail) / a[row][row];
+    }
+    x.iter().all(|x| x.is_finite()).then_some(x)
+}
+
+/// Validates `coefficients` from Python as the four HAR loadings.
+pub(crate) fn coefficients(values: &[f64]) -> PyResult<[f64; 4]> {
+    values
+        .try_into()
+        .map_err(|_| PyValueError::new_err("need [intercept, five_minute, hourly, daily]"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine;
+    use crate::innovations;
+
+    /// Returns whose variance each minute is the HAR prediction from the
+    /// squared returns before it.
+    fn simulate(coefficients: [f64; 4], n: usize, seed: u64) -> Vec<f64> {
+        let [c, short, hour, day] = coefficients;
+        let long_run = c / (1.0 - short - hour - day);
+        let mut rng = engine::path_rng(seed, 0);
+        let mut prefix = vec![0.0; n + 1];
+        let mut returns = Vec::with_capacity(n);
+        for t in 0..n {
+            let mean = |w: usize| {
+                if t < w {
+                    long_run
+                } else {
+                    (prefix[t] - prefix[t - w]) / w as f64
+                }
+            };
+            let v = c
+                + short * mean(COMPONENTS[0])
+                + hour * mean(COMPONENTS[1])
+                + day * mean(COMPONENTS[2]);
+            let r = v.sqrt() * innovations::standard_normal(&mut rng);
+            prefix[t + 1] = prefix[t] + r * r;
+            returns.push(r);
+        }
+        returns
+    }
+
+    #[test]
+    fn recovers_a_simulated_har_process() {
+        let truth = [2e-7, 0.3, 0.3, 0.2];
+        let fit = fit_har(&simulate(truth, 400_000, 1)).unwrap();
+        let [c, short, hour, day] = fit.har.coefficients;
+        // The regression forecasts 5 minutes ahead of a process defined a
+        // minute ahead, so the loadings only match roughly...
+        for (fitted, true_loading) in [short, hour, day].iter().zip(&truth[1..]) {
+            assert!((fitted - true_loading).abs() < 0.1, "{fit:?}");
+        }
+        // ...but persistence and the long-run variance closely.
+        assert!((short + hour + day - 0.8).abs() < 0.05, "{fit:?}");
+        let long_run = c / (1.0 - short - hour - day);
+        assert!((long_run / 1e-6 - 1.0).abs() < 0.1, "{fit:?}");
+        assert!(fit.r_squared > 0.05, "{fit:?}");
+    }
+
+    #[test]
+    fn singular_designs_are_refused() {
+        // Squared returns that never vary make every regressor a multiple
+        // of the intercept, however long the history.
+        let mut rng = engine::path_rng(2, 0);
+        for n in [3_000, 100_000, 1_000_000] {
+            let returns: Vec<f64> = (0..n)
+                .map(|_| 1e-3 * innovations::standard_normal(&mut rng).signum())
+                .collect();
+            assert!(fit_har(&returns).is_err(), "{n}");
+        }
+        assert!(fit_har(&[0.0; 3_000]).is_err());
+        assert!(fit_har(&[1e-3; 1_000]).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+// garch_monte_carlo/src/heston.rs
+// Heston stochastic volatility in minute units, and its calibration.
+
+use crate::array;
+use crate::innovations;
+use crate::model::Model;
+use crate::simulator::Simulator;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::prelude::*;
+
+/// dv = kappa (theta - v) dt + xi sqrt(v) dW_v, with dW_v correlated `rho`
+/// to the log-price shock. Time is in minutes and `v` is the variance of
+/// a 1-minute log return.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Heston {
+    pub kappa: f64,
+    pub theta: f64,
+    pub xi: f64,
+    pub rho: f64,
+}
+
+impl Heston {
+    pub(crate) fn new(kappa: f64, theta: f64, xi: f64, rho: f64) -> PyResult<Self> {
+        if !(kappa >= 0.0 && theta > 0.0 && xi >= 0.0 && rho.abs() <= 1.0) {
+            return Err(PyValueError::new_err(
+                "need kappa >= 0, theta > 0, xi >= 0 and -1 <= rho <= 1",
+            ));
+        }
+        Ok(Heston {
+            kappa,
+            theta,
+            xi,
+            rho,
+        })
+    }
+
+    /// Full-truncation Euler step of `dt` minutes from `v`, given the
+    /// standardized price shock `z` of the same step. The variance may go
+    /// negative, but only its positive part drives drift and diffusion.
+    pub(crate) fn step<R: Rng>(&self, rng: &mut R, v: f64, dt: f64, z: f64) -> f64 {
+        let v_pos = v.max(0.0);
+        let z_v =
+            self.rho * z + (1.0 - self.rho * self.rho).sqrt() * innovations::standard_normal(rng);
+        v + self.kappa * (self.theta - v_pos) * dt + self.xi * (v_pos * dt).sqrt() * z_v
+    }
+
+    /// Expected next variance given an observed standardized shock `z`:
+    /// only the part of the variance shock correlated with it is known.
+    pub(crate) fn update(&self, v: f64, z: f64) -> f64 {
+        let v_pos = v.max(0.0);
+        v + self.kappa * (self.theta - v_pos) + self.xi * v_pos.sqrt() * self.rho * z
+    }
+}
+
+/// Calibrated Heston parameters, plus the standardized returns the
+/// simulator resamples for the price shock.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct HestonFit {
+    heston: Heston,
+    /// Variance of the next minute: the last block's realized variance.
+    #[pyo3(get)]
+    pub v0: f64,
+    /// Realized-variance blocks the moments were computed from.
+    #[pyo3(get)]
+    pub num_blocks: usize,
+    std_residuals: Vec<f64>,
+}
+
+#[pymethods]
+impl HestonFit {
+    /// Mean reversion speed per minute.
+    #[getter]
+    fn kappa(&self) -> f64 {
+        self.heston.kappa
+    }
+
+    /// Long-run variance of a 1-minute return.
+    #[getter]
+    fn theta(&self) -> f64 {
+        self.heston.theta
+    }
+
+    /// Volatility of variance.
+    #[getter]
+    fn xi(&self) -> f64 {
+        self.heston.xi
+    }
+
+    /// Correlation of price and variance shocks.
+    #[getter]
+    fn rho(&self) -> f64 {
+        self.heston.rho
+    }
+
+    /// Whether 2 kappa theta >= xi^2, so the variance never reaches zero.
+    #[getter]
+    fn feller(&self) -> bool {
+        2.0 * self.heston.kappa * self.heston.theta >= self.heston.xi * self.heston.xi
+    }
+
+    /// Returns over the square root of the previous block's realized
+    /// variance, rescaled to unit variance, as a NumPy array.
+    #[getter]
+    fn std_residuals(&self, py: Python<'_>) -> PyResult<PyObject> {
+        array::to_numpy(py, self.std_residuals.clone())
+    }
+
+    /// A Heston `Simulator` starting from `v0`.
+    #[pyo3(signature = (window=None))]
+    fn simulator(&self, window: Option<usize>) -> PyResult<Simulator> {
+        let mut model = Model::heston(self.heston, self.std_residuals.clone(), self.v0)?;
+        model.window = window;
+        Ok(Simulator::new(model))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "HestonFit(kappa={:.6}, theta={:.6e}, xi={:.6e}, rho={:.4}, v0={:.6e}, num_blocks={})",
+            self.heston.kappa,
+            self.heston.theta,
+            self.heston.xi,
+            self.heston.rho,
+            self.v0,
+            self.num_blocks
+        )
+    }
+}
+
+/// Calibrates Heston to 1-minute `returns` by moments of realized variance
+/// over consecutive `block`-minute blocks (the first returns are dropped so
+/// the last block ends on the last return):
+///
+/// - kappa and theta from the AR(1) that block variances follow,
+///   instrumenting each block with the one before it so the measurement
+///   error in realized variance does not bias the slope;
+/// - xi from the AR(1) residual variance, net of that measurement error
+///   (taken as Gaussian, 2 v^2 / block) and scaled up for the smoothing
+///   that averaging over a block does;
+/// - rho from the leverage effect: the covariance of each return with the
+///   realized variance of the block after it, relative to what xi implies
+///   at full correlation.
+pub(crate) fn fit_heston(returns: &[f64], block: usize) -> PyResult<HestonFit> {
+    let block = block.max(1);
+    let num_blocks = returns.len() / block;
+    if num_blocks < 8 {
+        return Err(PyValueError::new_err(
+            "need at least 8 blocks of returns to calibrate",
+        ));
+    }
+    let returns = &returns[returns.len() - num_blocks * block..];
+    let m = block as f64;
+    let rv: Vec<f64> = returns
+        .chunks(block)
+        .map(|c| c.iter().map(|r| r * r).sum::<f64>() / m)
+        .collect();
+    let theta = rv.iter().sum::<f64>() / num_blocks as f64;
+    if !theta.is_finite() {
+        return Err(PyValueError::new_err("returns must be finite"));
+    }
+    if theta <= 0.0 {
+        return Err(PyValueError::new_err("returns are all zero"));
+    }
+
+    // v[k+1] = a + b v[k] + e, instrumented by v[k-1].
+    let d = |k: usize| rv[k] - theta;
+    let (mut cov_next, mut cov_now) = (0.0, 0.0);
+    for k in 1..num_blocks - 1 {
+        cov_next += d(k + 1) * d(k - 1);
+        cov_now += d(k) * d(k - 1);
+    }
+    let b = if cov_now > 0.0 {
+        (cov_next / cov_now).clamp(1e-6, 1.0 - 1e-6)
+    } else {
+        1e-6
+    };
+    let kappa = -b.ln() / m;
+
+    let mut excess = 0.0;
+    for k in 0..num_blocks - 1 {
+        let e = d(k + 1) - b * d(k);
+        let noise = 2.0 * (rv[k + 1] * rv[k + 1] + b * b * rv[k] * rv[k]) / m;
+        excess += e * e - noise;
+    }
+    let excess = excess.max(0.0) / (num_blocks - 1) as f64;
+    // For dv = -kappa (v - theta) dt + s dW, block means have variance
+    // s^2/(2 kappa) g0 and lag-one covariance s^2/(2 kappa) g1, and
+    // s^2 = xi^2 theta.
+    // 1 - b by expm1, so g0 and g1 keep their precision as b nears 1.
+    let x = kappa * m;
+    let one_minus_b = -(-x).exp_m1();
+    let g0 = 2.0 * (x - one_minus_b) / (x * x);
+    let g1 = (one_minus_b / x).powi(2);
+    let xi = (2.0 * kappa * excess / (theta * (g0 * (1.0 + b * b) - 2.0 * b * g1))).sqrt();
+
+    // Cov(r_t, v_{t+j}) = rho xi theta exp(-kappa (j - 1)), averaged over
+    // the block after t.
+    let mut prefix = vec![0.0; returns.len() + 1];
+    for (i, r) in returns.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + r * r;
+    }
+    let n = returns.len() - block;
+    let (mut sum_r, mut sum_v, mut sum_rv) = (0.0, 0.0, 0.0);
+    for (t, r) in returns[..n].iter().enumerate() {
+        let next = (prefix[t + 1 + block] - prefix[t + 1]) / m;
+        sum_r += r;
+        sum_v += next;
+        sum_rv += r * next;
+    }
+    let nf = n as f64;
+    let leverage = sum_rv / nf - (sum_r / nf) * (sum_v / nf);
+    let decay = (1.0 - b) / (m * (1.0 - (-kappa).exp()));
+    let rho = if xi > 0.0 {
+        (leverage / (xi * theta * decay)).clamp(-0.99, 0.99)
+    } else {
+        0.0
+    };
+    for (name, value) in [("kappa", kappa), ("xi", xi), ("rho", rho)] {
+        if !value.is_finite() {
+            return Err(PyValueError::new_err(format!(
+                "moment calibration gave a non-finite {name}: the {num_blocks} block variances \
+                 are too few or too flat to identify it; use more returns or a shorter block"
+            )));
+        }
+    }
+
+    let mut std_residuals: Vec<f64> = returns[block..]
+        .iter()
+        .enumerate()
+        .map(|(i, r)| r / rv[i / block].sqrt())
+        .filter(|z| z.is_finite())
+        .collect();
+    let rms =
+        (std_residuals.iter().map(|z| z * z).sum::<f64>() / std_residuals.len() as f64).sqrt();
+    std_residuals.iter_mut().for_each(|z| *z /= rms);
+
+    Ok(HestonFit {
+        heston: Heston::new(kappa, theta, xi, rho)?,
+        v0: rv[num_blocks - 1],
+        num_blocks,
+        std_residuals,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine;
+
+    /// `n` 1-minute returns of `heston` by Euler steps, started at theta.
+    fn simulate(heston: Heston, n: usize, seed: u64) -> Vec<f64> {
+        let mut rng = engine::path_rng(seed, 0);
+        let mut v = heston.theta;
+        (0..n)
+            .map(|_| {
+                let z = innovations::standard_normal(&mut rng);
+                let r = v.max(0.0).sqrt() * z;
+                v = heston.step(&mut rng, v, 1.0, z);
+                r
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recovers_heston_parameters() {
+        let heston = Heston::new(0.005, 1e-6, 5e-5, -0.5).unwrap();
+        let returns = simulate(heston, 500_000, 3);
+        let fit = fit_heston(&returns, 60).unwrap();
+        let got = fit.heston;
+        assert!((got.theta / heston.theta - 1.0).abs() < 0.05, "{got:?}");
+        assert!((got.kappa / heston.kappa - 1.0).abs() < 0.3, "{got:?}");
+        assert!((got.xi / heston.xi - 1.0).abs() < 0.2, "{got:?}");
+        assert!((got.rho - heston.rho).abs() < 0.15, "{got:?}");
+    }
+
+    #[test]
+    fn rejects_non_finite_and_short_input() {
+        let mut returns = simulate(Heston::new(0.005, 1e-6, 5e-5, 0.0).unwrap(), 6000, 4);
+        assert!(fit_heston(&returns[..7 * 60], 60).is_err());
+        returns[100] = f64::INFINITY;
+        assert!(fit_heston(&returns, 60).is_err());
+        returns[100] = f64::NAN;
+        assert!(fit_heston(&returns, 60).is_err());
+        assert!(fit_heston(&vec![0.0; 6000], 60).is_err());
+    }
+}
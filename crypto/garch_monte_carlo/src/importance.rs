@@ -0,0 +1,341 @@
+// garch_monte_carlo/src/importance.rs
+// Importance sampling of exceedance probabilities far from the strike.
+
+use crate::engine::{self, Run};
+use crate::innovations::{self, Innovations, SkewT, StudentQuantiles};
+use crate::model::{Horizon, Model};
+use crate::result::SimulationResult;
+use crate::stats::{self, Moments};
+use rand::prelude::*;
+
+/// Share of resampled shocks at either end the tilted mean is kept within,
+/// so the tilt never piles all the weight on a handful of outliers.
+const TILT_QUANTILE: f64 = 0.001;
+
+/// Walker's alias table for drawing index `j` with probability proportional
+/// to `weights[j]` in constant time.
+struct Alias {
+    /// Probability of keeping the column drawn rather than its alias.
+    keep: Vec<f64>,
+    alias: Vec<usize>,
+}
+
+impl Alias {
+    /// Vose's construction.
+    fn new(weights: &[f64]) -> Self {
+        let n = weights.len();
+        let total: f64 = weights.iter().sum();
+        let mut keep: Vec<f64> = weights.iter().map(|w| w * n as f64 / total).collect();
+        let mut alias: Vec<usize> = (0..n).collect();
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) = (0..n).partition(|&j| keep[j] < 1.0);
+        while let (Some(&s), Some(&l)) = (small.last(), large.last()) {
+            small.pop();
+            alias[s] = l;
+            keep[l] -= 1.0 - keep[s];
+            if keep[l] < 1.0 {
+                large.pop();
+                small.push(l);
+            }
+        }
+        // Whatever is left is 1 up to rounding.
+        for j in small.into_iter().chain(large) {
+            keep[j] = 1.0;
+        }
+        Alias { keep, alias }
+    }
+
+    fn draw<R: Rng>(&self, rng: &mut R) -> usize {
+        let j = rng.gen_range(0..self.keep.len());
+        if rng.gen::<f64>() < self.keep[j] {
+            j
+        } else {
+            self.alias[j]
+        }
+    }
+}
+
+/// What the shocks are drawn from instead of the model's innovations.
+enum Tilt {
+    /// Resampled shock `s` made `exp(theta s)` times as likely: exponential
+    /// tilting of the empirical distribution. `log_mgf` is the log of the
+    /// mean of `exp(theta s)` over the shocks.
+    Exponential {
+        theta: f64,
+        log_mgf: f64,
+        alias: Alias,
+    },
+    /// Parametric shocks drawn as the skewed-t quantile of a normal score
+    /// moved by `shift`: Gaussian tilting of the score, which the quantile
+    /// map carries monotonically onto the shock.
+    Score {
+        dist: SkewT,
+        student: StudentQuantiles,
+        loc: f64,
+        scale: f64,
+        shift: f64,
+    },
+}
+
+impl Tilt {
+    /// One shock from the tilted distribution, and the log of its
+    /// likelihood ratio (model over tilted density).
+    fn draw<R: Rng>(&self, rng: &mut R, shocks: &[f64]) -> (f64, f64) {
+        match self {
+            Tilt::Exponential {
+                theta,
+                log_mgf,
+                alias,
+            } => {
+                let s = shocks[alias.draw(rng)];
+                (s, log_mgf - theta * s)
+            }
+            Tilt::Score {
+                dist,
+                student,
+                loc,
+                scale,
+                shift,
+            } => {
+                let score = innovations::standard_normal(rng) + shift;
+                let z = score_quantile(dist, student, score);
+                (loc + scale * z, shift * (0.5 * shift - score))
+            }
+        }
+    }
+}
+
+/// Skewed-t quantile at the probability of normal score `score`, kept
+/// finite where that probability rounds to 0 or 1.
+fn score_quantile(dist: &SkewT, student: &StudentQuantiles, score: f64) -> f64 {
+    let u = innovations::normal_cdf(score).clamp(f64::MIN_POSITIVE, 1.0 - 0.5 * f64::EPSILON);
+    dist.quantile(student, u)
+}
+
+/// The score shift whose skewed-t quantiles have mean `target`, by
+/// bisection with the mean taken by quadrature over the normal density.
+fn solve_shift(dist: &SkewT, student: &StudentQuantiles, target: f64) -> f64 {
+    const STEP: f64 = 1.0 / 64.0;
+    let mean_at = |shift: f64| {
+        (-640..=640)
+            .map(|i| {
+                let g = i as f64 * STEP;
+                let density = (-0.5 * g * g).exp() * STEP / (2.0 * std::f64::consts::PI).sqrt();
+                density * score_quantile(dist, student, g + shift)
+            })
+            .sum::<f64>()
+    };
+    let (mut lo, mut hi) = (-8.0, 8.0);
+    for _ in 0..50 {
+        let mid = 0.5 * (lo + hi);
+        if mean_at(mid) < target {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    0.5 * (lo + hi)
+}
+
+/// Log of the mean of `exp(theta s)` over `shocks`, and the mean of `s`
+/// under those weights, computed relative to `reference` to avoid overflow.
+fn tilted_moments(shocks: &[f64], theta: f64, reference: f64) -> (f64, f64, f64) {
+    let (mut sum_w, mut sum_ws, mut sum_wss) = (0.0, 0.0, 0.0);
+    for &s in shocks {
+        let w = (theta * (s - reference)).exp();
+        sum_w += w;
+        sum_ws += w * s;
+        sum_wss += w * s * s;
+    }
+    let mean = sum_ws / sum_w;
+    let log_mgf = theta * reference + (sum_w / shocks.len() as f64).ln();
+    (log_mgf, mean, (sum_wss / sum_w - mean * mean).max(0.0))
+}
+
+/// The `theta` whose tilted mean is `target`, by Newton's method kept
+/// inside a bracket that shrinks around the root. `target` lies strictly
+/// between the smallest and largest shock.
+fn solve_theta(shocks: &[f64], target: f64, spread: f64) -> f64 {
+    let (lo_s, hi_s) = shocks
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &s| {
+            (lo.min(s), hi.max(s))
+        });
+    let mean_at = |theta: f64| {
+        let reference = if theta >= 0.0 { hi_s } else { lo_s };
+        tilted_moments(shocks, theta, reference)
+    };
+    // Widen a bracket from zero until it holds the root.
+    let (_, mean, _) = mean_at(0.0);
+    let direction = if target > mean { 1.0 } else { -1.0 };
+    let mut outer = direction / spread;
+    for _ in 0..64 {
+        if direction * (mean_at(outer).1 - target) >= 0.0 {
+            break;
+        }
+        outer *= 2.0;
+    }
+    let (mut lo, mut hi) = if direction > 0.0 {
+        (0.0, outer)
+    } else {
+        (outer, 0.0)
+    };
+    let mut theta = 0.0;
+    for _ in 0..100 {
+        let (_, mean, variance) = mean_at(theta);
+        let gap = mean - target;
+        if gap.abs() <= 1e-10 * spread {
+            break;
+        }
+        if gap < 0.0 {
+            lo = theta;
+        } else {
+            hi = theta;
+        }
+        let newton = theta - gap / variance;
+        theta = if newton > lo && newton < hi && variance > 0.0 {
+            newton
+        } else {
+            0.5 * (lo + hi)
+        };
+    }
+    theta
+}
+
+impl<'a> Model<'a> {
+    /// `probability` by importance sampling, for strikes far out in either
+    /// tail. Shocks are drawn from their distribution tilted so that, with
+    /// volatility frozen at its current level, the log-return is centred on
+    /// the strike; each path is weighted by the product of its shocks'
+    /// likelihood ratios. The rarer side of the strike is estimated, so its
+    /// relative error stays bounded as `p` approaches 0 or 1. Resampled
+    /// shocks are tilted exponentially, parametric ones through the normal
+    /// score their quantile is taken at. Jumps, Heston variance and regime
+    /// switches keep their own laws.
+    pub(crate) fn probability_tilted(
+        &self,
+        current_price: f64,
+        target_price: f64,
+        horizon: Horizon,
+        run: &Run,
+    ) -> SimulationResult {
+        let threshold = (target_price / current_price).ln();
+        let weights = self.frozen_scales(horizon);
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return self.probability(current_price, target_price, horizon, run);
+        }
+        // Shock mean that puts the frozen-volatility log-return on the
+        // strike.
+        let target = threshold / total;
+
+        let (tilt, center) = match self.innovations {
+            Innovations::Bootstrap => {
+                let moments = Moments::of(&self.shocks);
+                let mut sorted = self.shocks.to_vec();
+                sorted.sort_unstable_by(f64::total_cmp);
+                let target = target.clamp(
+                    stats::quantile_sorted(&sorted, TILT_QUANTILE),
+                    stats::quantile_sorted(&sorted, 1.0 - TILT_QUANTILE),
+                );
+                let spread = moments.variance.sqrt().max(f64::MIN_POSITIVE);
+                let theta = solve_theta(&self.shocks, target, spread);
+                let reference = if theta >= 0.0 {
+                    sorted[sorted.len() - 1]
+                } else {
+                    sorted[0]
+                };
+                let (log_mgf, _, _) = tilted_moments(&self.shocks, theta, reference);
+                let tilted: Vec<f64> = self
+                    .shocks
+                    .iter()
+                    .map(|s| (theta * (s - reference)).exp())
+                    .collect();
+                let tilt = Tilt::Exponential {
+                    theta,
+                    log_mgf,
+                    alias: Alias::new(&tilted),
+                };
+                (tilt, moments.mean)
+            }
+            Innovations::Parametric { dist, loc, scale } => {
+                let student = StudentQuantiles::new(dist.dof);
+                let shift = solve_shift(&dist, &student, (target - loc) / scale);
+                let tilt = Tilt::Score {
+                    dist,
+                    student,
+                    loc,
+                    scale,
+                    shift,
+                };
+                (tilt, loc)
+            }
+        };
+        // Estimate whichever side of the strike is the rare one.
+        let above = threshold >= center * total;
+
+        let (tally, simulated) = engine::mean_until(run, |rng| {
+            let mut log_weight = 0.0;
+            let x = self.walk_shocks(
+                rng,
+                horizon,
+                |rng| {
+                    let (shock, log_ratio) = tilt.draw(rng, &self.shocks);
+                    log_weight += log_ratio;
+                    shock
+                },
+                |_, _, _| true,
+            );
+            if (x > threshold) == above {
+                log_weight.exp()
+            } else {
+                0.0
+            }
+        });
+        let rare = tally.mean(simulated);
+        let std_error = tally.std_error(simulated);
+        let p = if above { rare } else { 1.0 - rare }.clamp(0.0, 1.0);
+        let factor = p * (1.0 - p) / simulated as f64 / (std_error * std_error);
+        SimulationResult::from_estimate(
+            p,
+            std_error,
+            simulated,
+            run.seed,
+            run.started.elapsed(),
+            factor,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::array::F64Array;
+    use crate::exact::ExactBootstrap;
+    use crate::innovations;
+
+    #[test]
+    fn deep_tail_matches_the_exact_bootstrap() {
+        let mut rng = engine::path_rng(12, 0);
+        let returns: Vec<f64> = (0..5000)
+            .map(|_| {
+                let z = innovations::standard_normal(&mut rng);
+                1e-3 * (z + 0.2 * (z * z - 1.0))
+            })
+            .collect();
+        let exact = ExactBootstrap::from_returns(F64Array::Owned(returns.clone()), 64.0).unwrap();
+        let model = Model::bootstrap(returns).unwrap();
+        let horizon = Horizon::from_seconds(900.0).unwrap();
+        let run = Run::new(50_000, Some(13), None, None).unwrap();
+        // About four standard deviations out on either side.
+        for target in [98.5, 101.6] {
+            let p = exact.survival(horizon, (target / 100.0f64).ln());
+            assert!(p.min(1.0 - p) < 1e-3, "target {target}: {p}");
+            let tilted = model.probability_tilted(100.0, target, horizon, &run);
+            let gap = (tilted.probability - p).abs();
+            assert!(gap < 3.0 * tilted.std_error, "target {target}: {gap}");
+            // What plain sampling's standard error would be at as many paths.
+            let plain = (p * (1.0 - p) / tilted.num_simulations as f64).sqrt();
+            assert!(tilted.std_error < plain, "target {target}");
+        }
+    }
+}
@@ -0,0 +1,560 @@
+// garch_monte_carlo/src/innovations.rs
+// Parametric shock distributions as an alternative to resampling history.
+
+use crate::optimize;
+use crate::stats::Moments;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::prelude::*;
+use std::f64::consts::PI;
+
+/// Hansen's (1994) skewed-t, standardized to zero mean and unit variance.
+/// `skew` in (-1, 1) puts more mass on the right when positive; at zero it
+/// is the standardized Student-t. Requires `dof > 2`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SkewT {
+    pub dof: f64,
+    pub skew: f64,
+}
+
+impl SkewT {
+    pub(crate) fn new(dof: f64, skew: f64) -> PyResult<Self> {
+        if dof.is_nan() || dof <= 2.0 || skew.is_nan() || skew.abs() >= 1.0 {
+            return Err(PyValueError::new_err(
+                "need dof > 2 and -1 < skew < 1 for a unit-variance skewed-t",
+            ));
+        }
+        Ok(SkewT { dof, skew })
+    }
+
+    /// Hansen's `(a, b, c)`: location and scale of the transformed variable
+    /// and the Student-t normalizing constant.
+    pub(crate) fn constants(&self) -> (f64, f64, f64) {
+        let nu = self.dof;
+        let c = (ln_gamma(0.5 * (nu + 1.0)) - ln_gamma(0.5 * nu)).exp() / (PI * (nu - 2.0)).sqrt();
+        let a = 4.0 * self.skew * c * (nu - 2.0) / (nu - 1.0);
+        let b = (1.0 + 3.0 * self.skew * self.skew - a * a).sqrt();
+        (a, b, c)
+    }
+
+    /// Log-density at each of `values`, summed.
+    fn log_likelihood(&self, values: &[f64]) -> f64 {
+        let nu = self.dof;
+        let (a, b, c) = self.constants();
+        let sum: f64 = values.iter().map(|&z| self.log_core(a, b, z)).sum();
+        values.len() as f64 * (b * c).ln() - 0.5 * (nu + 1.0) * sum
+    }
+
+    /// The part of the log-density that varies with `z`, given Hansen's `a`
+    /// and `b`: the log-density is `ln(b c) - (dof + 1) / 2` times this.
+    pub(crate) fn log_core(&self, a: f64, b: f64, z: f64) -> f64 {
+        let s = if z < -a / b {
+            1.0 - self.skew
+        } else {
+            1.0 + self.skew
+        };
+        let y = (b * z + a) / s;
+        (1.0 + y * y / (self.dof - 2.0)).ln()
+    }
+
+    /// One draw: a unit-variance Student-t magnitude, put on the left with
+    /// probability (1 - skew)/2 and stretched by (1 -/+ skew), then
+    /// recentred and rescaled.
+    pub(crate) fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        let nu = self.dof;
+        let t = standard_normal(rng) * ((nu - 2.0) / (2.0 * gamma(rng, 0.5 * nu))).sqrt();
+        if self.skew == 0.0 {
+            return t;
+        }
+        let (a, b, _) = self.constants();
+        let y = if rng.gen::<f64>() < 0.5 * (1.0 - self.skew) {
+            -(1.0 - self.skew) * t.abs()
+        } else {
+            (1.0 + self.skew) * t.abs()
+        };
+        (y - a) / b
+    }
+
+    /// Maximum likelihood fit to the standardized `values`, keeping `dof`
+    /// and `skew` where given. A Student-t fit (`skewed` false) keeps skew
+    /// at zero.
+    pub(crate) fn fit(
+        values: &[f64],
+        skewed: bool,
+        dof: Option<f64>,
+        skew: Option<f64>,
+    ) -> PyResult<Self> {
+        if values.len() < 2 {
+            return Err(PyValueError::new_err(
+                "need at least two shocks to fit a distribution",
+            ));
+        }
+        let skew = if skewed { skew } else { Some(0.0) };
+        let fixed = SkewT::new(dof.unwrap_or(6.0), skew.unwrap_or(0.0))?;
+        if dof.is_some() && skew.is_some() {
+            return Ok(fixed);
+        }
+
+        let moments = Moments::of(values);
+        let sd = moments.variance.sqrt();
+        let standardized: Vec<f64> = values.iter().map(|x| (x - moments.mean) / sd).collect();
+        // Unconstrained: ln(dof - 2) and atanh(skew), for the free ones.
+        let unpack = |theta: &[f64]| {
+            let mut free = theta.iter();
+            SkewT {
+                dof: dof.unwrap_or_else(|| 2.0 + free.next().unwrap().exp()),
+                skew: skew.unwrap_or_else(|| free.next().unwrap().tanh()),
+            }
+        };
+        let start: Vec<f64> = [
+            dof.is_none().then(|| (fixed.dof - 2.0).ln()),
+            skew.is_none().then_some(0.0),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        let minimum = optimize::nelder_mead(
+            |theta| -unpack(theta).log_likelihood(&standardized) / standardized.len() as f64,
+            &start,
+            0.5,
+            1e-9,
+            1000,
+        );
+        let fitted = unpack(&minimum.x);
+        SkewT::new(fitted.dof.min(1e6), fitted.skew)
+    }
+}
+
+/// Unit-variance Student-t quantiles, tabulated against the standard normal
+/// quantile of the same probability so one table covers both tails.
+#[derive(Clone, Debug)]
+pub(crate) struct StudentQuantiles {
+    /// Quantile at normal scores `0, 1/STEPS, 2/STEPS, ...`.
+    table: Vec<f64>,
+}
+
+impl StudentQuantiles {
+    const STEPS: f64 = 256.0;
+    /// Largest normal score tabulated; a 53-bit uniform stays below 8.3.
+    const MAX_SCORE: f64 = 9.0;
+
+    /// Inverts the t CDF at every grid point by Newton's method on the log
+    /// tail probability, starting from the previous point.
+    pub(crate) fn new(dof: f64) -> Self {
+        let nu = dof;
+        let log_density_constant =
+            ln_gamma(0.5 * (nu + 1.0)) - ln_gamma(0.5 * nu) - 0.5 * (nu * PI).ln();
+        // P(T > q) for q >= 0.
+        let tail = |q: f64| {
+            let q2 = q * q;
+            0.5 * incomplete_beta(0.5 * nu, 0.5, nu / (nu + q2), q2 / (nu + q2))
+        };
+        let unit = ((nu - 2.0) / nu).sqrt();
+        let points = (Self::MAX_SCORE * Self::STEPS) as usize + 1;
+        let mut table = Vec::with_capacity(points);
+        let mut q = 0.0;
+        for i in 0..points {
+            let target = normal_cdf(-(i as f64) / Self::STEPS);
+            for _ in 0..100 {
+                let t = tail(q);
+                let density =
+                    (log_density_constant - 0.5 * (nu + 1.0) * (q * q / nu).ln_1p()).exp();
+                let step = (t.ln() - target.ln()) * t / density;
+                q = (q + step).max(0.5 * q);
+                if step.abs() <= 1e-12 * (1.0 + q) {
+                    break;
+                }
+            }
+            table.push(unit * q);
+        }
+        StudentQuantiles { table }
+    }
+
+    /// Quantile at probability `u` in (0, 1).
+    pub(crate) fn at(&self, u: f64) -> f64 {
+        let score = normal_quantile(u);
+        let position = (score.abs() * Self::STEPS).min((self.table.len() - 1) as f64);
+        let i = (position as usize).min(self.table.len() - 2);
+        let frac = position - i as f64;
+        let q = self.table[i] + frac * (self.table[i + 1] - self.table[i]);
+        q.copysign(score)
+    }
+}
+
+impl SkewT {
+    /// Quantile at probability `u` in (0, 1), from Hansen's construction:
+    /// the Student-t quantile of `u` rescaled within the side it falls on.
+    pub(crate) fn quantile(&self, student: &StudentQuantiles, u: f64) -> f64 {
+        if self.skew == 0.0 {
+            return student.at(u);
+        }
+        let (a, b, _) = self.constants();
+        let left = 0.5 * (1.0 - self.skew);
+        let y = if u < left {
+            (1.0 - self.skew) * student.at(u / (1.0 - self.skew))
+        } else {
+            (1.0 + self.skew) * student.at(0.5 + (u - left) / (1.0 + self.skew))
+        };
+        (y - a) / b
+    }
+}
+
+/// Regularized incomplete beta I_x(a, b), given `x` and `y = 1 - x`
+/// separately so tails near either end keep their precision.
+fn incomplete_beta(a: f64, b: f64, x: f64, y: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if y <= 0.0 {
+        return 1.0;
+    }
+    let front = (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * y.ln()).exp();
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_fraction(b, a, y) / b
+    }
+}
+
+/// Continued fraction of the incomplete beta by the modified Lentz method.
+fn beta_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    d = 1.0 / if d.abs() < TINY { TINY } else { d };
+    let mut h = d;
+    for m in 1..300 {
+        let m = m as f64;
+        for numerator in [
+            m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            d = 1.0 / if d.abs() < TINY { TINY } else { d };
+            c = 1.0 + numerator / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            h *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-15 {
+            break;
+        }
+    }
+    h
+}
+
+/// Where each simulated step's standardized shock comes from.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) enum Innovations {
+    /// Resample the model's historical shocks.
+    #[default]
+    Bootstrap,
+    /// Draw `loc + scale * z` with `z` from `dist`. For raw returns `loc` and
+    /// `scale` are their mean and standard deviation; standardized
+    /// residuals use 0 and 1.
+    Parametric { dist: SkewT, loc: f64, scale: f64 },
+}
+
+impl Innovations {
+    /// `"bootstrap"`, `"t"` or `"skewt"`.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Innovations::Bootstrap => "bootstrap",
+            Innovations::Parametric { dist, .. } if dist.skew == 0.0 => "t",
+            Innovations::Parametric { .. } => "skewt",
+        }
+    }
+}
+
+/// Inverse CDF of a model's innovations, for shocks driven by a uniform
+/// chosen by the caller.
+pub(crate) enum InverseCdf {
+    /// Resampled shocks, sorted: a uniform picks the one at its rank.
+    Empirical(Vec<f64>),
+    Parametric {
+        dist: SkewT,
+        student: StudentQuantiles,
+        loc: f64,
+        scale: f64,
+    },
+}
+
+impl InverseCdf {
+    pub(crate) fn new(innovations: Innovations, shocks: &[f64]) -> Self {
+        match innovations {
+            Innovations::Bootstrap => {
+                let mut sorted = shocks.to_vec();
+                sorted.sort_unstable_by(f64::total_cmp);
+                InverseCdf::Empirical(sorted)
+            }
+            Innovations::Parametric { dist, loc, scale } => InverseCdf::Parametric {
+                dist,
+                student: StudentQuantiles::new(dist.dof),
+                loc,
+                scale,
+            },
+        }
+    }
+
+    /// The shock at probability `u` in (0, 1).
+    pub(crate) fn shock(&self, u: f64) -> f64 {
+        match self {
+            InverseCdf::Empirical(sorted) => {
+                sorted[((u * sorted.len() as f64) as usize).min(sorted.len() - 1)]
+            }
+            InverseCdf::Parametric {
+                dist,
+                student,
+                loc,
+                scale,
+            } => loc + scale * dist.quantile(student, u),
+        }
+    }
+}
+
+/// Marsaglia's polar method.
+pub(crate) fn standard_normal<R: Rng>(rng: &mut R) -> f64 {
+    loop {
+        let u = 2.0 * rng.gen::<f64>() - 1.0;
+        let v = 2.0 * rng.gen::<f64>() - 1.0;
+        let s = u * u + v * v;
+        if s > 0.0 && s < 1.0 {
+            return u * (-2.0 * s.ln() / s).sqrt();
+        }
+    }
+}
+
+/// Standard normal CDF, to double precision (Hart's algorithm as given
+/// by West, 2005, below 7.07 standard deviations).
+pub(crate) fn normal_cdf(x: f64) -> f64 {
+    let z = x.abs();
+    let tail = if z > 37.0 {
+        0.0
+    } else if z < 7.071_067_811_865_47 {
+        let e = (-0.5 * z * z).exp();
+        let numerator = [
+            0.700_383_064_443_688,
+            6.373_962_203_531_65,
+            33.912_866_078_383,
+            112.079_291_497_871,
+            221.213_596_169_931,
+            220.206_867_912_376,
+        ]
+        .iter()
+        .fold(3.526_249_659_989_11e-2, |b, c| b * z + c);
+        let denominator = [
+            1.755_667_163_182_64,
+            16.064_177_579_207,
+            86.780_732_202_946_1,
+            296.564_248_779_674,
+            637.333_633_378_831,
+            793.826_512_519_948,
+            440.413_735_824_752,
+        ]
+        .iter()
+        .fold(8.838_834_764_831_84e-2, |b, c| b * z + c);
+        e * numerator / denominator
+    } else {
+        // Laplace's continued fraction for the Mills ratio; West's five
+        // terms leave a relative error near 1e-8 here.
+        let e = (-0.5 * z * z).exp();
+        let fraction = (1..=20).rev().fold(z, |b, c| z + c as f64 / b);
+        e / fraction / (2.0 * PI).sqrt()
+    };
+    if x > 0.0 {
+        1.0 - tail
+    } else {
+        tail
+    }
+}
+
+/// Standard normal quantile for `p` in (0, 1), to about 1e-16 (Wichura's
+/// AS 241).
+pub(crate) fn normal_quantile(p: f64) -> f64 {
+    fn poly(coefficients: &[f64], x: f64) -> f64 {
+        coefficients.iter().rev().fold(0.0, |acc, c| acc * x + c)
+    }
+    const A: [f64; 8] = [
+        3.387_132_872_796_366_5,
+        133.141_667_891_784_38,
+        1_971.590_950_306_551_3,
+        13_731.693_765_509_46,
+        45_921.953_931_549_87,
+        67_265.770_927_008_7,
+        33_430.575_583_588_13,
+        2_509.080_928_730_122_7,
+    ];
+    const B: [f64; 8] = [
+        1.0,
+        42.313_330_701_600_91,
+        687.187_007_492_057_9,
+        5_394.196_021_424_751,
+        21_213.794_301_586_597,
+        39_307.895_800_092_71,
+        28_729.085_735_721_943,
+        5_226.495_278_852_854,
+    ];
+    const C: [f64; 8] = [
+        1.423_437_110_749_683_5,
+        4.630_337_846_156_546,
+        5.769_497_221_460_691,
+        3.647_848_324_763_204_5,
+        1.270_458_252_452_368_4,
+        0.241_780_725_177_450_6,
+        2.272_384_498_926_918_4e-2,
+        7.745_450_142_783_414e-4,
+    ];
+    const D: [f64; 8] = [
+        1.0,
+        2.053_191_626_637_759,
+        1.676_384_830_183_803_8,
+        0.689_767_334_985_1,
+        0.148_103_976_427_480_08,
+        1.519_866_656_361_645_7e-2,
+        5.475_938_084_995_345e-4,
+        1.050_750_071_644_416_9e-9,
+    ];
+    const E: [f64; 8] = [
+        6.657_904_643_501_103,
+        5.463_784_911_164_114,
+        1.784_826_539_917_291_3,
+        0.296_560_571_828_504_87,
+        2.653_218_952_657_612_4e-2,
+        1.242_660_947_388_078_4e-3,
+        2.711_555_568_743_487_6e-5,
+        2.010_334_399_292_288_1e-7,
+    ];
+    const F: [f64; 8] = [
+        1.0,
+        0.599_832_206_555_888,
+        0.136_929_880_922_735_8,
+        1.487_536_129_085_061_5e-2,
+        7.868_691_311_456_133e-4,
+        1.846_318_317_510_054_8e-5,
+        1.421_511_758_316_446e-7,
+        2.044_263_103_389_939_7e-15,
+    ];
+    let q = p - 0.5;
+    if q.abs() <= 0.425 {
+        let r = 0.180_625 - q * q;
+        return q * poly(&A, r) / poly(&B, r);
+    }
+    let r = if q < 0.0 { p } else { 1.0 - p };
+    let r = (-r.ln()).sqrt();
+    let x = if r <= 5.0 {
+        poly(&C, r - 1.6) / poly(&D, r - 1.6)
+    } else {
+        poly(&E, r - 5.0) / poly(&F, r - 5.0)
+    };
+    if q < 0.0 {
+        -x
+    } else {
+        x
+    }
+}
+
+/// Gamma(`shape`, 1) draw by Marsaglia and Tsang's method, `shape >= 1`.
+fn gamma<R: Rng>(rng: &mut R, shape: f64) -> f64 {
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / (9.0 * d).sqrt();
+    loop {
+        let x = standard_normal(rng);
+        let v = (1.0 + c * x).powi(3);
+        if v <= 0.0 {
+            continue;
+        }
+        let u: f64 = rng.gen();
+        if u.ln() < 0.5 * x * x + d - d * v + d * v.ln() {
+            return d * v;
+        }
+    }
+}
+
+/// Natural log of the gamma function for `x > 0` (Lanczos, g = 7).
+pub(crate) fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection formula.
+        return (PI / (PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let mut sum = COEFFICIENTS[0];
+    for (i, &coefficient) in COEFFICIENTS.iter().enumerate().skip(1) {
+        sum += coefficient / (x + i as f64);
+    }
+    let t = x + 7.5;
+    0.5 * (2.0 * PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normal_quantile_matches_known_values() {
+        for (p, q) in [
+            (0.5, 0.0),
+            (0.841_344_746_068_542_9, 1.0),
+            (0.975, 1.959_963_984_540_054),
+            (0.995, 2.575_829_303_548_900_4),
+            (1e-10, -6.361_340_902_404_056),
+        ] {
+            assert!((normal_quantile(p) - q).abs() < 1e-12, "p {p}");
+            // 1 - upper is exact, so the tails must mirror exactly.
+            let upper = 1.0 - p;
+            let mirrored = normal_quantile(upper) + normal_quantile(1.0 - upper);
+            assert!(mirrored.abs() < 1e-12, "p {p}");
+        }
+        for p in [1e-200, 1e-20, 0.01, 0.3, 0.7, 0.99] {
+            let roundtrip = normal_cdf(normal_quantile(p));
+            assert!((roundtrip / p - 1.0).abs() < 1e-12, "p {p}");
+        }
+    }
+
+    #[test]
+    fn student_quantiles_match_known_values() {
+        // (dof, u, standard Student-t quantile)
+        for (dof, u, q) in [
+            (3.0, 0.99, 4.540_702_858_698_419),
+            (5.0, 0.975, 2.570_581_835_636_314),
+            (5.0, 0.995, 4.032_142_983_557_536),
+            (10.0, 0.95, 1.812_461_122_811_676),
+        ] {
+            let student = StudentQuantiles::new(dof);
+            let unit = q * ((dof - 2.0) / dof).sqrt();
+            assert!((student.at(u) / unit - 1.0).abs() < 1e-4, "dof {dof} u {u}");
+            assert!(
+                (student.at(1.0 - u) / unit + 1.0).abs() < 1e-4,
+                "dof {dof} u {u}"
+            );
+        }
+        assert_eq!(StudentQuantiles::new(5.0).at(0.5), 0.0);
+    }
+
+    #[test]
+    fn skewed_quantile_splits_at_the_mode() {
+        let student = StudentQuantiles::new(5.0);
+        let symmetric = SkewT::new(5.0, 0.0).unwrap();
+        for u in [0.01, 0.2, 0.5, 0.9] {
+            assert_eq!(symmetric.quantile(&student, u), student.at(u));
+        }
+        // Hansen's skewed-t puts (1 - skew)/2 of its mass below -a/b.
+        for skew in [-0.3, 0.4] {
+            let dist = SkewT::new(5.0, skew).unwrap();
+            let (a, b, _) = dist.constants();
+            let mode = dist.quantile(&student, 0.5 * (1.0 - skew));
+            assert!((mode + a / b).abs() < 1e-9, "skew {skew}");
+        }
+    }
+}
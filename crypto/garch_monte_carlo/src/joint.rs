@@ -0,0 +1,218 @@
+// garch_monte_carlo/src/joint.rs
+// Several assets simulated together by resampling shared timestamps.
+
+use crate::block::{Blocks, Cursor};
+use crate::engine::{self, Run};
+use crate::innovations::Innovations;
+use crate::model::{Horizon, Model};
+use crate::result::JointResult;
+use crate::seasonality::Clock;
+use crate::simulator::Simulator;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::sync::Arc;
+
+/// Most assets a joint simulation can have, so outcomes fit a bitmask.
+const MAX_ASSETS: usize = 16;
+
+/// One `Simulator` per asset, stepped together: every simulated minute
+/// resamples the same historical index for all assets, so their shocks
+/// keep the cross-sectional dependence of that minute. Each asset keeps its
+/// own dynamics (plain, GARCH, Heston, ...), seasonality and jumps.
+#[pyclass]
+pub struct JointSimulator {
+    names: Vec<String>,
+    assets: Vec<Arc<Model<'static>>>,
+    /// How the shared indices are strung together.
+    blocks: Blocks,
+    now: Clock,
+}
+
+impl JointSimulator {
+    /// Paths per outcome, bit `i` set when asset `i` ended above
+    /// `thresholds[i]` (a log-return), and the number of paths.
+    fn outcomes(&self, thresholds: &[f64], horizon: Horizon, run: &Run) -> (Vec<usize>, usize) {
+        let m = self.assets.len();
+        let num_steps = horizon.steps().count();
+        let n = self.assets[0].shocks.len();
+        let ((counts, _), simulated) = engine::fold_paths_until(
+            run,
+            || (vec![0usize; 1 << m], Vec::with_capacity(num_steps)),
+            |(counts, indices), rng| {
+                let mut cursor = Cursor::default();
+                indices.clear();
+                indices.extend((0..num_steps).map(|_| self.blocks.next_index(rng, n, &mut cursor)));
+                let mut outcome = 0;
+                for (i, asset) in self.assets.iter().enumerate() {
+                    let mut step = indices.iter();
+                    let x = asset.walk_shocks(
+                        rng,
+                        horizon,
+                        |_| asset.shocks[*step.next().unwrap()],
+                        |_, _, _| true,
+                    );
+                    outcome |= ((x > thresholds[i]) as usize) << i;
+                }
+                counts[outcome] += 1;
+            },
+            |(mut a, scratch), (b, _)| {
+                a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                (a, scratch)
+            },
+            |(counts, _), n| {
+                counts
+                    .iter()
+                    .map(|&hits| engine::hit_std_error(hits, n))
+                    .fold(0.0, f64::max)
+            },
+        );
+        (counts, simulated)
+    }
+}
+
+#[pymethods]
+impl JointSimulator {
+    /// Joins `simulators`, one per asset in `names`. Their returns or
+    /// residuals must be aligned: the same length, with index `t` the same
+    /// minute for every asset. Takes a snapshot, so rebuild it after
+    /// `append_return` on the simulators. `block` and `block_length` are as
+    /// in `Simulator.set_block_bootstrap`, with the automatic length taken
+    /// from the first asset.
+    #[staticmethod]
+    #[pyo3(signature = (names, simulators, block="iid", block_length=None))]
+    fn from_simulators(
+        names: Vec<String>,
+        simulators: Vec<PyRef<Simulator>>,
+        block: &str,
+        block_length: Option<f64>,
+    ) -> PyResult<Self> {
+        if names.len() != simulators.len() || names.is_empty() || names.len() > MAX_ASSETS {
+            return Err(PyValueError::new_err(
+                "need one name per simulator, and 1 to 16 of them",
+            ));
+        }
+        let assets: Vec<Arc<Model<'static>>> = simulators.iter().map(|s| s.model()).collect();
+        let n = assets[0].shocks.len();
+        if assets.iter().any(|a| a.shocks.len() != n) {
+            return Err(PyValueError::new_err(
+                "simulators must hold equally many aligned shocks",
+            ));
+        }
+        if assets
+            .iter()
+            .any(|a| !matches!(a.innovations, Innovations::Bootstrap))
+        {
+            return Err(PyValueError::new_err(
+                "joint simulation resamples shocks; set innovations back to 'bootstrap'",
+            ));
+        }
+        let blocks = Blocks::parse(block, block_length, &assets[0].shocks)?;
+        Ok(JointSimulator {
+            names,
+            assets,
+            blocks,
+            now: Clock::default(),
+        })
+    }
+
+    #[getter]
+    fn names(&self) -> Vec<String> {
+        self.names.clone()
+    }
+
+    /// Wall-clock Unix time (seconds) simulations start from, for assets
+    /// with seasonality; `None` reads the system clock.
+    #[getter]
+    fn now(&self) -> Option<f64> {
+        self.now.get()
+    }
+
+    #[setter]
+    fn set_now(&self, now: Option<f64>) -> PyResult<()> {
+        self.now.set(now)
+    }
+
+    /// Joint probabilities of each asset ending above its target after
+    /// `horizon_seconds`, `current_prices` and `target_prices` in `names`
+    /// order. Adaptive stopping targets the largest standard error over the
+    /// outcome combinations.
+    #[pyo3(signature = (
+        current_prices, target_prices, horizon_seconds, num_simulations, seed=None,
+        target_std_error=None, max_seconds=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn probability(
+        &self,
+        py: Python<'_>,
+        current_prices: Vec<f64>,
+        target_prices: Vec<f64>,
+        horizon_seconds: f64,
+        num_simulations: usize,
+        seed: Option<u64>,
+        target_std_error: Option<f64>,
+        max_seconds: Option<f64>,
+    ) -> PyResult<JointResult> {
+        let m = self.assets.len();
+        if current_prices.len() != m || target_prices.len() != m {
+            return Err(PyValueError::new_err(
+                "need one current and one target price per asset",
+            ));
+        }
+        let thresholds: Vec<f64> = current_prices
+            .iter()
+            .zip(&target_prices)
+            .map(|(current, target)| (target / current).ln())
+            .collect();
+        let run = Run::new(num_simulations, seed, target_std_error, max_seconds)?;
+        let horizon = Horizon {
+            start: Some(self.now.now()),
+            ..Horizon::from_seconds(horizon_seconds)?
+        };
+        let (counts, simulated) = py.allow_threads(|| self.outcomes(&thresholds, horizon, &run));
+        Ok(JointResult::new(
+            self.names.clone(),
+            counts,
+            simulated,
+            run.seed,
+            run.started.elapsed(),
+        ))
+    }
+
+    fn __len__(&self) -> usize {
+        self.assets.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::innovations;
+
+    #[test]
+    fn perfectly_correlated_assets_move_together() {
+        let mut rng = engine::path_rng(2, 0);
+        let returns: Vec<f64> = (0..1000)
+            .map(|_| 1e-3 * innovations::standard_normal(&mut rng))
+            .collect();
+        // The second asset moves twice as far on every shared minute.
+        let doubled: Vec<f64> = returns.iter().map(|r| 2.0 * r).collect();
+        let joint = JointSimulator {
+            names: vec!["A".into(), "B".into()],
+            assets: vec![
+                Arc::new(Model::bootstrap(returns).unwrap()),
+                Arc::new(Model::bootstrap(doubled).unwrap()),
+            ],
+            blocks: Blocks::Iid,
+            now: Clock::default(),
+        };
+        let horizon = Horizon::from_seconds(600.0).unwrap();
+        let run = Run::new(50_000, Some(4), None, None).unwrap();
+        let (counts, simulated) = joint.outcomes(&[1e-3, 2e-3], horizon, &run);
+        assert_eq!(simulated, 50_000);
+        // Only "both down" and "both up" occur, so P(A and B) equals each
+        // marginal.
+        assert_eq!(counts[0b01], 0);
+        assert_eq!(counts[0b10], 0);
+        assert!(counts[0b11] > 0 && counts[0b00] > 0);
+    }
+}
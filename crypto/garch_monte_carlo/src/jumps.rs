@@ -0,0 +1,172 @@
+// garch_monte_carlo/src/jumps.rs
+// Compound-Poisson jumps on top of the diffusive minute returns.
+
+use crate::array::F64Array;
+use crate::innovations;
+use crate::stats::Moments;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::prelude::*;
+use std::f64::consts::FRAC_PI_2;
+
+/// Merton jumps: a Poisson number of normal log-price jumps per minute,
+/// added to the simulated returns without feeding back into the variance.
+/// They come on top of the diffusion, so its history should exclude the
+/// detected jumps (see `detect`).
+#[pyclass(get_all)]
+#[derive(Clone, Copy, Debug)]
+pub struct Jumps {
+    /// Expected jumps per minute.
+    pub intensity: f64,
+    /// Mean log-return of a jump.
+    pub mean: f64,
+    /// Standard deviation of a jump's log-return.
+    pub std: f64,
+}
+
+impl Jumps {
+    /// Total log-return of the jumps within a step of `dt` minutes.
+    pub(crate) fn sample<R: Rng>(&self, rng: &mut R, dt: f64) -> f64 {
+        // Knuth's method; the expected count per step is tiny.
+        let threshold = (-self.intensity * dt).exp();
+        let mut count = 0u32;
+        let mut product: f64 = rng.gen();
+        while product > threshold {
+            count += 1;
+            product *= rng.gen::<f64>();
+        }
+        if count == 0 {
+            return 0.0;
+        }
+        let n = count as f64;
+        n * self.mean + n.sqrt() * self.std * innovations::standard_normal(rng)
+    }
+}
+
+/// Indices of returns larger than `threshold` local standard deviations,
+/// the local variance being the bipower variation of the `window` returns
+/// before, which is robust to jumps among them. The first `window` returns
+/// are never flagged.
+fn detect(returns: &[f64], threshold: f64, window: usize) -> Vec<usize> {
+    let window = window.max(2);
+    if returns.len() <= window {
+        return Vec::new();
+    }
+    let products: Vec<f64> = returns
+        .windows(2)
+        .map(|pair| pair[0].abs() * pair[1].abs())
+        .collect();
+    // products[i] pairs returns i and i + 1; return t uses the window - 1
+    // products among returns t - window .. t.
+    let mut sum: f64 = products[..window - 1].iter().sum();
+    let mut flagged = Vec::new();
+    for t in window..returns.len() {
+        let variance = FRAC_PI_2 * sum / (window - 1) as f64;
+        if variance > 0.0 && returns[t].abs() > threshold * variance.sqrt() {
+            flagged.push(t);
+        }
+        sum += products[t - 1] - products[t - window];
+    }
+    flagged
+}
+
+#[pymethods]
+impl Jumps {
+    /// Jumps with the given intensity per minute and normal log-size.
+    #[staticmethod]
+    fn merton(intensity: f64, mean: f64, std: f64) -> PyResult<Self> {
+        let valid =
+            [intensity, mean, std].iter().all(|x| x.is_finite()) && intensity >= 0.0 && std >= 0.0;
+        if !valid {
+            return Err(PyValueError::new_err(
+                "jump intensity and std must be finite and non-negative, and mean finite",
+            ));
+        }
+        Ok(Jumps {
+            intensity,
+            mean,
+            std,
+        })
+    }
+
+    /// Fits intensity and size distribution to the returns that `detect`
+    /// flags: intensity is flagged returns per minute screened, size the
+    /// flagged returns' mean and standard deviation.
+    #[staticmethod]
+    #[pyo3(signature = (returns, threshold=4.0, window=60))]
+    fn estimate(returns: F64Array, threshold: f64, window: usize) -> Self {
+        let returns = returns.as_slice();
+        let flagged = detect(returns, threshold, window);
+        let screened = returns.len().saturating_sub(window.max(2));
+        if flagged.is_empty() {
+            return Jumps {
+                intensity: 0.0,
+                mean: 0.0,
+                std: 0.0,
+            };
+        }
+        let sizes: Vec<f64> = flagged.iter().map(|&t| returns[t]).collect();
+        let moments = Moments::of(&sizes);
+        Jumps {
+            intensity: sizes.len() as f64 / screened as f64,
+            mean: moments.mean,
+            std: moments.variance.sqrt(),
+        }
+    }
+
+    /// Indices of the returns `estimate` treats as jumps; drop them from
+    /// the history the simulator resamples or fits on.
+    #[staticmethod]
+    #[pyo3(name = "detect", signature = (returns, threshold=4.0, window=60))]
+    fn detect_py(returns: F64Array, threshold: f64, window: usize) -> Vec<usize> {
+        detect(returns.as_slice(), threshold, window)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Jumps(intensity={:.6}, mean={:.6}, std={:.6})",
+            self.intensity, self.mean, self.std
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine;
+
+    #[test]
+    fn rejects_non_finite_parameters() {
+        assert!(Jumps::merton(0.01, -0.002, 0.004).is_ok());
+        assert!(Jumps::merton(f64::INFINITY, 0.0, 0.01).is_err());
+        assert!(Jumps::merton(f64::NAN, 0.0, 0.01).is_err());
+        assert!(Jumps::merton(-0.01, 0.0, 0.01).is_err());
+        assert!(Jumps::merton(0.01, f64::NAN, 0.01).is_err());
+        assert!(Jumps::merton(0.01, 0.0, f64::INFINITY).is_err());
+    }
+
+    #[test]
+    fn estimates_jumps_injected_into_gaussian_returns() {
+        let sd = 1e-3;
+        let truth = Jumps::merton(0.002, 0.01, 0.002).unwrap();
+        let mut rng = engine::path_rng(8, 0);
+        let returns: Vec<f64> = (0..200_000)
+            .map(|_| sd * innovations::standard_normal(&mut rng) + truth.sample(&mut rng, 1.0))
+            .collect();
+        // At 5 standard deviations about 0.1 Gaussian minutes are flagged.
+        let flagged = detect(&returns, 5.0, 60);
+        let fit = Jumps::estimate(F64Array::Owned(returns), 5.0, 60);
+        assert_eq!(
+            flagged.len(),
+            (fit.intensity * (200_000 - 60) as f64).round() as usize
+        );
+        assert!(
+            (fit.intensity / truth.intensity - 1.0).abs() < 0.15,
+            "{fit:?}"
+        );
+        assert!((fit.mean / truth.mean - 1.0).abs() < 0.05, "{fit:?}");
+        // A flagged minute's return includes its diffusive part.
+        let size_sd = truth.std.hypot(sd);
+        assert!((fit.std / size_sd - 1.0).abs() < 0.15, "{fit:?}");
+    }
+}
@@ -1,94 +1,211 @@
 // garch_monte_carlo/src/lib.rs
 // Cargo.toml dependencies:
 // [dependencies]
-// pyo3 = { version = "0.20", features = ["extension-module"] }
+// pyo3 = "0.20"
+// numpy = "0.20"
 // rayon = "1.8"
 // rand = "0.8"
 // rand_xoshiro = "0.6"
+// [features]
+// extension-module = ["pyo3/extension-module"]
 
+mod array;
+mod block;
+mod engine;
+mod exact;
+mod fit;
+mod har;
+mod heston;
+mod importance;
+mod innovations;
+mod joint;
+mod jumps;
+mod model;
+mod optimize;
+mod qmc;
+mod reduction;
+mod regime;
+mod result;
+mod seasonality;
+mod simulator;
+mod stats;
+mod variance;
+
+use array::F64Array;
+use block::Blocks;
+use engine::Run;
+use exact::ExactBootstrap;
+use fit::GarchFit;
+use har::HarFit;
+use heston::HestonFit;
+use joint::JointSimulator;
+use jumps::Jumps;
+use model::{Horizon, Model};
 use pyo3::prelude::*;
-use rand::prelude::*;
-use rand_xoshiro::Xoshiro256PlusPlus;
-use rayon::prelude::*;
+use reduction::Reduction;
+use regime::RegimeFit;
+use result::{DistributionResult, JointResult, LadderResult, SimulationResult};
+use seasonality::Seasonality;
+use simulator::Simulator;
+use variance::{VarianceKind, VarianceModel};
 
 #[pyfunction]
+#[pyo3(signature = (
+    omega, alpha, beta, last_resid, last_sigma_sq, residuals,
+    current_price, target_price, horizon_seconds, num_simulations, seed=None,
+    target_std_error=None, max_seconds=None, antithetic=false, control_variate=false, importance_sampling=false
+))]
+#[allow(clippy::too_many_arguments)]
 fn calculate_probability_only(
+    py: Python<'_>,
     omega: f64,
     alpha: f64,
     beta: f64,
     last_resid: f64,
     last_sigma_sq: f64,
-    residuals: Vec<f64>,
+    residuals: F64Array,
     current_price: f64,
     target_price: f64,
-    horizon_minutes: usize,
+    horizon_seconds: f64,
     num_simulations: usize,
-) -> PyResult<f64> {
-    let initial_sigma_sq = omega + alpha * last_resid.powi(2) + beta * last_sigma_sq;
-    let residuals_len = residuals.len();
-
-    // Count successes without storing all prices (saves memory)
-    let count_above: usize = (0..num_simulations)
-        .into_par_iter()
-        .map_init(
-            || Xoshiro256PlusPlus::from_entropy(),
-            |rng, _| {
-                let mut price = current_price;
-                let mut current_sigma_sq = initial_sigma_sq;
-
-                for _ in 0..horizon_minutes {
-                    let idx = rng.gen_range(0..residuals_len);
-                    let shock = residuals[idx];
-                    let sigma = current_sigma_sq.sqrt();
-                    let simulated_return = sigma * shock;
-                    price *= (simulated_return).exp();
-                    current_sigma_sq = omega + alpha * shock * shock + beta * current_sigma_sq;
-                }
-
-                (price > target_price) as usize
-            },
-        )
-        .sum();
-
-    Ok(count_above as f64 / num_simulations as f64)
+    seed: Option<u64>,
+    target_std_error: Option<f64>,
+    max_seconds: Option<f64>,
+    antithetic: bool,
+    control_variate: bool,
+    importance_sampling: bool,
+) -> PyResult<SimulationResult> {
+    let run = Run::new(num_simulations, seed, target_std_error, max_seconds)?;
+    let horizon = Horizon::from_seconds(horizon_seconds)?;
+    let garch = VarianceModel::Garch { omega, alpha, beta };
+    let model = Model::filtered(garch, residuals.as_slice(), last_resid, last_sigma_sq)?;
+    let reduction = Reduction {
+        antithetic,
+        control_variate,
+        importance: importance_sampling,
+    };
+    py.allow_threads(|| {
+        model.probability_with(current_price, target_price, horizon, reduction, &run)
+    })
 }
 
+/// Bootstrap of raw 1-minute log returns. `block="stationary"` or
+/// `"circular"` resamples runs of consecutive returns of (mean)
+/// `block_length`, estimated from the returns when not given.
+/// `antithetic`, `control_variate` and `importance_sampling` are as in
+/// `Simulator.probability`.
 #[pyfunction]
+#[pyo3(signature = (
+    returns, current_price, target_price, horizon_seconds, num_simulations, seed=None,
+    target_std_error=None, max_seconds=None, block="iid", block_length=None,
+    antithetic=false, control_variate=false, importance_sampling=false
+))]
+#[allow(clippy::too_many_arguments)]
 fn calculate_probability_plain(
-    returns: Vec<f64>,
+    py: Python<'_>,
+    returns: F64Array,
     current_price: f64,
     target_price: f64,
-    horizon_minutes: usize,
+    horizon_seconds: f64,
     num_simulations: usize,
-) -> PyResult<f64> {
-    let returns_len = returns.len();
+    seed: Option<u64>,
+    target_std_error: Option<f64>,
+    max_seconds: Option<f64>,
+    block: &str,
+    block_length: Option<f64>,
+    antithetic: bool,
+    control_variate: bool,
+    importance_sampling: bool,
+) -> PyResult<SimulationResult> {
+    let run = Run::new(num_simulations, seed, target_std_error, max_seconds)?;
+    let horizon = Horizon::from_seconds(horizon_seconds)?;
+    let mut model = Model::bootstrap(returns.as_slice())?;
+    model.blocks = Blocks::parse(block, block_length, &model.shocks)?;
+    let reduction = Reduction {
+        antithetic,
+        control_variate,
+        importance: importance_sampling,
+    };
+    py.allow_threads(|| {
+        model.probability_with(current_price, target_price, horizon, reduction, &run)
+    })
+}
+
+/// Gaussian QMLE of a zero-mean GARCH(1,1), GJR-GARCH(1,1) (`model="gjr"`)
+/// or EGARCH(1,1) (`model="egarch"`) on 1-minute log returns.
+#[pyfunction]
+#[pyo3(signature = (returns, model="garch", max_iterations=2000))]
+fn fit_garch(
+    py: Python<'_>,
+    returns: F64Array,
+    model: &str,
+    max_iterations: usize,
+) -> PyResult<GarchFit> {
+    let kind = VarianceKind::parse(model)?;
+    let returns = returns.as_slice();
+    py.allow_threads(|| fit::fit(kind, returns, max_iterations))
+}
 
-    let count_above: usize = (0..num_simulations)
-        .into_par_iter()
-        .map_init(
-            || Xoshiro256PlusPlus::from_entropy(),
-            |rng, _| {
-                let mut price = current_price;
+/// Calibrates Heston stochastic volatility to 1-minute log returns from
+/// `block`-minute realized variances.
+#[pyfunction]
+#[pyo3(signature = (returns, block=60))]
+fn fit_heston(py: Python<'_>, returns: F64Array, block: usize) -> PyResult<HestonFit> {
+    let returns = returns.as_slice();
+    py.allow_threads(|| heston::fit_heston(returns, block))
+}
 
-                for _ in 0..horizon_minutes {
-                    let idx = rng.gen_range(0..returns_len);
-                    let simulated_return = returns[idx];
-                    price *= 1.0 + simulated_return;
-                }
+/// Fits a HAR-RV regression of 5-minute realized variance on its
+/// 5-minute, hourly and daily means to 1-minute log returns.
+#[pyfunction]
+fn fit_har(py: Python<'_>, returns: F64Array) -> PyResult<HarFit> {
+    let returns = returns.as_slice();
+    py.allow_threads(|| har::fit_har(returns))
+}
 
-                (price > target_price) as usize
-            },
-        )
-        .sum();
+/// Fits a 1- to 3-regime Markov switching volatility model to 1-minute
+/// log returns by EM.
+#[pyfunction]
+#[pyo3(signature = (returns, num_regimes=2, max_iterations=500))]
+fn fit_regimes(
+    py: Python<'_>,
+    returns: F64Array,
+    num_regimes: usize,
+    max_iterations: usize,
+) -> PyResult<RegimeFit> {
+    let returns = returns.as_slice();
+    py.allow_threads(|| regime::fit_regimes(returns, num_regimes, max_iterations))
+}
 
-    Ok(count_above as f64 / num_simulations as f64)
+/// Politis-White block lengths `(stationary, circular)` for `values`,
+/// computed on `|values|` unless `absolute` is false.
+#[pyfunction]
+#[pyo3(signature = (values, absolute=true))]
+fn optimal_block_length(values: F64Array, absolute: bool) -> (f64, f64) {
+    block::optimal_block_length(values.as_slice(), absolute)
 }
 
 #[pymodule]
 fn garch_monte_carlo(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(calculate_probability_plain, m)?)?;
     m.add_function(wrap_pyfunction!(calculate_probability_only, m)?)?;
+    m.add_function(wrap_pyfunction!(fit_garch, m)?)?;
+    m.add_function(wrap_pyfunction!(fit_heston, m)?)?;
+    m.add_function(wrap_pyfunction!(fit_regimes, m)?)?;
+    m.add_function(wrap_pyfunction!(fit_har, m)?)?;
+    m.add_function(wrap_pyfunction!(optimal_block_length, m)?)?;
+    m.add_class::<SimulationResult>()?;
+    m.add_class::<LadderResult>()?;
+    m.add_class::<DistributionResult>()?;
+    m.add_class::<JointResult>()?;
+    m.add_class::<Simulator>()?;
+    m.add_class::<JointSimulator>()?;
+    m.add_class::<ExactBootstrap>()?;
+    m.add_class::<GarchFit>()?;
+    m.add_class::<HestonFit>()?;
+    m.add_class::<RegimeFit>()?;
+    m.add_class::<HarFit>()?;
+    m.add_class::<Seasonality>()?;
+    m.add_class::<Jumps>()?;
     Ok(())
 }
-
-
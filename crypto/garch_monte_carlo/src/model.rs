@@ -0,0 +1,713 @@
+// garch_monte_carlo/src/model.rs
+// Price dynamics shared by the pyfunctions and the Simulator pyclass.
+
+use crate::block::{Blocks, Cursor};
+use crate::engine::{self, Run};
+use crate::har::Har;
+use crate::heston::Heston;
+use crate::innovations::{Innovations, SkewT};
+use crate::jumps::Jumps;
+use crate::regime::{Regimes, MAX_REGIMES};
+use crate::result::{DistributionResult, LadderResult, SimulationResult};
+use crate::seasonality::Seasonality;
+use crate::stats::{self, Moments};
+use crate::variance::VarianceModel;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::prelude::*;
+use rayon::slice::ParallelSliceMut;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+/// Longest horizon simulated, in minutes (a year).
+const MAX_MINUTES: f64 = 366.0 * 24.0 * 60.0;
+
+/// `seconds` in minutes, checked to be a horizon that can be simulated.
+fn minutes(seconds: f64) -> PyResult<f64> {
+    if seconds.is_nan() || seconds < 0.0 {
+        return Err(PyValueError::new_err(
+            "horizon_seconds must be a non-negative number",
+        ));
+    }
+    let minutes = seconds / 60.0;
+    if minutes > MAX_MINUTES {
+        return Err(PyValueError::new_err(format!(
+            "horizon_seconds must be at most {} (a year)",
+            MAX_MINUTES * 60.0
+        )));
+    }
+    Ok(minutes)
+}
+
+/// Time to expiry as the rest of the current minute, whole minutes, and a
+/// final partial minute if expiry is not on a minute boundary.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Horizon {
+    /// Fraction of the current minute still to run, in [0, 1].
+    pub head: f64,
+    pub whole: usize,
+    /// Fraction of the minute in which expiry falls, in [0, 1).
+    pub tail: f64,
+    /// Log-return already realised in the current minute, if known.
+    pub minute_return: Option<f64>,
+    /// Unix time in seconds at which the horizon starts, for seasonality.
+    pub start: Option<f64>,
+}
+
+impl Horizon {
+    /// Assumes expiry is on a minute boundary, so the remainder of
+    /// `seconds` is what is left of the current minute. A zero horizon
+    /// simulates no steps at all.
+    pub(crate) fn from_seconds(seconds: f64) -> PyResult<Self> {
+        let minutes = minutes(seconds)?;
+        let whole = minutes.floor();
+        Ok(Horizon {
+            head: minutes - whole,
+            whole: whole as usize,
+            ..Horizon::default()
+        })
+    }
+
+    /// `elapsed_seconds` into the current minute, which has returned
+    /// `minute_return` so far.
+    pub(crate) fn within_minute(
+        seconds: f64,
+        elapsed_seconds: f64,
+        minute_return: f64,
+    ) -> PyResult<Self> {
+        let minutes = minutes(seconds)?;
+        if !elapsed_seconds.is_finite() || !minute_return.is_finite() {
+            return Err(PyValueError::new_err(
+                "elapsed_seconds and minute_return must be finite",
+            ));
+        }
+        if minutes == 0.0 {
+            return Ok(Horizon::default());
+        }
+        let head = (1.0 - elapsed_seconds / 60.0).clamp(0.0, 1.0).min(minutes);
+        let rest = minutes - head;
+        let whole = rest.floor();
+        Ok(Horizon {
+            head,
+            whole: whole as usize,
+            tail: rest - whole,
+            minute_return: Some(minute_return),
+            start: None,
+        })
+    }
+
+    /// Step lengths in minutes: head, whole minutes, tail, skipping empty
+    /// partial steps.
+    pub(crate) fn steps(&self) -> impl Iterator<Item = f64> {
+        (self.head > 0.0)
+            .then_some(self.head)
+            .into_iter()
+            .chain(std::iter::repeat_n(1.0, self.whole))
+            .chain((self.tail > 0.0).then_some(self.tail))
+    }
+
+    /// `steps` paired with the volatility factor of the wall-clock minute
+    /// each one falls in, or 1 without a profile or start time.
+    pub(crate) fn seasonal_steps<'s>(
+        &self,
+        seasonality: Option<&'s Seasonality>,
+    ) -> impl Iterator<Item = (f64, f64)> + 's {
+        let start = self.start;
+        self.steps().scan(0.0, move |elapsed, dt| {
+            let factor = match (seasonality, start) {
+                (Some(profile), Some(start)) => {
+                    profile.factor(start + 60.0 * (*elapsed + 0.5 * dt))
+                }
+                _ => 1.0,
+            };
+            *elapsed += dt;
+            Some((dt, factor))
+        })
+    }
+
+    /// Residual of the whole current minute once its remaining `resid` has
+    /// been simulated, both deseasonalized by the minute's `factor`. Without
+    /// an observed `minute_return`, the part already elapsed enters at its
+    /// expected size under variance `sigma_sq`.
+    fn close_minute(&self, sigma_sq: f64, resid: f64, factor: f64) -> f64 {
+        match self.minute_return {
+            Some(r) => r / factor + resid,
+            None => resid.signum() * ((1.0 - self.head) * sigma_sq + resid * resid).sqrt(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum Dynamics {
+    /// Historical 1-minute log returns, resampled i.i.d.
+    Bootstrap,
+    /// Filtered historical simulation: standardized residuals scaled by a
+    /// conditional volatility. `sigma_sq` is the variance of the next minute.
+    Filtered {
+        variance: VarianceModel,
+        sigma_sq: f64,
+    },
+    /// Heston stochastic volatility: standardized shocks scaled by a
+    /// variance `v` that follows its own correlated diffusion.
+    Heston { heston: Heston, v: f64 },
+    /// Markov regime switching: standardized shocks scaled by the
+    /// volatility of a hidden regime. `probs` are the regime probabilities
+    /// of the next minute.
+    Regime {
+        regimes: Regimes,
+        probs: [f64; MAX_REGIMES],
+    },
+    /// Standardized shocks scaled by a HAR-RV forecast of each coming
+    /// minute's variance, which the simulated path does not feed back into.
+    Har { har: Har },
+}
+
+/// Borrows its shocks for one-off pyfunction calls and owns them inside a
+/// `Simulator`.
+#[derive(Clone, Debug)]
+pub(crate) struct Model<'a> {
+    pub dynamics: Dynamics,
+    /// Returns for `Bootstrap`, standardized residuals for `Filtered`.
+    pub shocks: Cow<'a, [f64]>,
+    /// Keep at most this many shocks, dropping the oldest.
+    pub window: Option<usize>,
+    /// How the bootstrap strings resampled shocks together.
+    pub blocks: Blocks,
+    pub innovations: Innovations,
+    /// Volatility profile the simulated minutes are rescaled by; the shocks
+    /// are then deseasonalized.
+    pub seasonality: Option<Arc<Seasonality>>,
+    /// Compound-Poisson jumps added to every step.
+    pub jumps: Option<Jumps>,
+}
+
+impl<'a> Model<'a> {
+    pub(crate) fn bootstrap(returns: impl Into<Cow<'a, [f64]>>) -> PyResult<Self> {
+        Self::new(Dynamics::Bootstrap, returns)
+    }
+
+    pub(crate) fn filtered(
+        variance: VarianceModel,
+        residuals: impl Into<Cow<'a, [f64]>>,
+        last_resid: f64,
+        last_sigma_sq: f64,
+    ) -> PyResult<Self> {
+        let sigma_sq = variance.next_variance(last_sigma_sq, last_resid);
+        Self::new(Dynamics::Filtered { variance, sigma_sq }, residuals)
+    }
+
+    /// Heston with standardized `residuals` for the price shock and `v` as
+    /// the variance of the next minute.
+    pub(crate) fn heston(
+        heston: Heston,
+        residuals: impl Into<Cow<'a, [f64]>>,
+        v: f64,
+    ) -> PyResult<Self> {
+        Self::new(Dynamics::Heston { heston, v }, residuals)
+    }
+
+    /// Regime switching with standardized `residuals` and `probs` as the
+    /// regime probabilities of the next minute.
+    pub(crate) fn regimes(
+        regimes: Regimes,
+        residuals: impl Into<Cow<'a, [f64]>>,
+        probs: [f64; MAX_REGIMES],
+    ) -> PyResult<Self> {
+        Self::new(Dynamics::Regime { regimes, probs }, residuals)
+    }
+
+    /// HAR-RV forecasts scaling standardized `residuals`.
+    pub(crate) fn har(har: Har, residuals: impl Into<Cow<'a, [f64]>>) -> PyResult<Self> {
+        Self::new(Dynamics::Har { har }, residuals)
+    }
+
+    fn new(dynamics: Dynamics, shocks: impl Into<Cow<'a, [f64]>>) -> PyResult<Self> {
+        let shocks = shocks.into();
+        if shocks.is_empty() {
+            return Err(PyValueError::new_err(
+                "need at least one return or residual to resample",
+            ));
+        }
+        Ok(Model {
+            dynamics,
+            shocks,
+            window: None,
+            blocks: Blocks::Iid,
+            innovations: Innovations::Bootstrap,
+            seasonality: None,
+            jumps: None,
+        })
+    }
+
+    /// Draws shocks from `dist` instead of resampling them, or goes back to
+    /// the bootstrap with `None`.
+    pub(crate) fn set_innovations(&mut self, dist: Option<SkewT>) {
+        self.innovations = match dist {
+            None => Innovations::Bootstrap,
+            Some(dist) => match self.dynamics {
+                Dynamics::Bootstrap => {
+                    let moments = Moments::of(&self.shocks);
+                    Innovations::Parametric {
+                        dist,
+                        loc: moments.mean,
+                        scale: moments.variance.sqrt(),
+                    }
+                }
+                Dynamics::Filtered { .. }
+                | Dynamics::Heston { .. }
+                | Dynamics::Regime { .. }
+                | Dynamics::Har { .. } => Innovations::Parametric {
+                    dist,
+                    loc: 0.0,
+                    scale: 1.0,
+                },
+            },
+        };
+    }
+
+    /// One shock for a unit-length step, continuing the path's block at
+    /// `cursor` when resampling.
+    fn draw<R: Rng>(&self, rng: &mut R, cursor: &mut Cursor) -> f64 {
+        match self.innovations {
+            Innovations::Bootstrap => {
+                self.shocks[self.blocks.next_index(rng, self.shocks.len(), cursor)]
+            }
+            Innovations::Parametric { dist, loc, scale } => loc + scale * dist.sample(rng),
+        }
+    }
+
+    /// Jump log-return within a step of `dt` minutes, if jumps are on.
+    fn jump<R: Rng>(&self, rng: &mut R, dt: f64) -> f64 {
+        self.jumps.map_or(0.0, |jumps| jumps.sample(rng, dt))
+    }
+
+    /// Variance of the shocks `draw` returns.
+    fn shock_variance(&self) -> f64 {
+        match self.innovations {
+            Innovations::Bootstrap => Moments::of(&self.shocks).variance,
+            Innovations::Parametric { scale, .. } => scale * scale,
+        }
+    }
+
+    /// Rolls the model forward by one observed 1-minute log return.
+    pub(crate) fn append_return(&mut self, r: f64) {
+        match &mut self.dynamics {
+            Dynamics::Bootstrap => self.shocks.to_mut().push(r),
+            Dynamics::Filtered { variance, sigma_sq } => {
+                self.shocks.to_mut().push(r / sigma_sq.sqrt());
+                *sigma_sq = variance.next_variance(*sigma_sq, r);
+            }
+            Dynamics::Heston { heston, v } => {
+                let z = r / v.max(f64::MIN_POSITIVE).sqrt();
+                self.shocks.to_mut().push(z);
+                *v = heston.update(*v, z);
+            }
+            Dynamics::Regime { regimes, probs } => {
+                let (posterior, _) = regimes.filter(probs, r);
+                self.shocks
+                    .to_mut()
+                    .push(r / regimes.variance(&posterior).sqrt());
+                *probs = regimes.predict(&posterior);
+            }
+            Dynamics::Har { har } => {
+                self.shocks.to_mut().push(r / har.variance(0).sqrt());
+                har.push(r);
+            }
+        }
+        if let Some(window) = self.window {
+            if self.shocks.len() > window {
+                let excess = self.shocks.len() - window;
+                self.shocks.to_mut().drain(..excess);
+            }
+        }
+        if let (Dynamics::Bootstrap, Innovations::Parametric { dist, .. }) =
+            (&self.dynamics, self.innovations)
+        {
+            self.set_innovations(Some(dist));
+        }
+    }
+
+    /// Simulates one path over `horizon` and returns its log-return.
+    /// `visit(x_prev, x_next, scale_sq)` sees every step, where `x` is the
+    /// cumulative log-return and `scale_sq` the factor the step's shock
+    /// variance was scaled by (the step length in minutes times the squared
+    /// seasonal factor for the bootstrap); returning false ends the path
+    /// early.
+    pub(crate) fn walk<R, V>(&self, rng: &mut R, horizon: Horizon, visit: V) -> f64
+    where
+        R: Rng,
+        V: FnMut(f64, f64, f64) -> bool,
+    {
+        let mut cursor = Cursor::default();
+        self.walk_shocks(rng, horizon, |rng| self.draw(rng, &mut cursor), visit)
+    }
+
+    /// `walk` with each step's shock (a return or standardized residual,
+    /// as `shocks` holds) taken from `shock` instead of the model's draws.
+    pub(crate) fn walk_shocks<R, S, V>(
+        &self,
+        rng: &mut R,
+        horizon: Horizon,
+        mut shock: S,
+        mut visit: V,
+    ) -> f64
+    where
+        R: Rng,
+        S: FnMut(&mut R) -> f64,
+        V: FnMut(f64, f64, f64) -> bool,
+    {
+        let mut x = 0.0;
+        let steps = horizon.seasonal_steps(self.seasonality.as_deref());
+        match self.dynamics {
+            Dynamics::Bootstrap => {
+                for (dt, factor) in steps {
+                    let next = x + factor * dt.sqrt() * shock(rng) + self.jump(rng, dt);
+                    let keep_going = visit(x, next, dt * factor * factor);
+                    x = next;
+                    if !keep_going {
+                        break;
+                    }
+                }
+            }
+            Dynamics::Filtered { variance, sigma_sq } => {
+                let mut sigma_sq = sigma_sq;
+                for (k, (dt, factor)) in steps.enumerate() {
+                    let resid = (dt * sigma_sq).sqrt() * shock(rng);
+                    let next = x + factor * resid + self.jump(rng, dt);
+                    let keep_going = visit(x, next, dt * sigma_sq * factor * factor);
+                    x = next;
+                    if !keep_going {
+                        break;
+                    }
+                    let minute_resid = if k == 0 && horizon.head > 0.0 {
+                        horizon.close_minute(sigma_sq, resid, factor)
+                    } else {
+                        resid
+                    };
+                    sigma_sq = variance.next_variance(sigma_sq, minute_resid);
+                }
+            }
+            Dynamics::Heston { heston, v } => {
+                let mut v = v;
+                for (dt, factor) in steps {
+                    let v_pos = v.max(0.0);
+                    let z = shock(rng);
+                    let next = x + factor * (dt * v_pos).sqrt() * z + self.jump(rng, dt);
+                    let keep_going = visit(x, next, dt * v_pos * factor * factor);
+                    x = next;
+                    if !keep_going {
+                        break;
+                    }
+                    v = heston.step(rng, v, dt, z);
+                }
+            }
+            Dynamics::Regime { regimes, probs } => {
+                // What the current minute has returned so far is evidence
+                // about its regime.
+                let probs = match horizon.minute_return {
+                    Some(r) if horizon.head > 0.0 && horizon.head < 1.0 => {
+                        regimes.filter(&probs, r / (1.0 - horizon.head).sqrt()).0
+                    }
+                    _ => probs,
+                };
+                let mut regime = regimes.draw(rng, &probs);
+                for (dt, factor) in steps {
+                    let variance = regimes.variances[regime];
+                    let next =
+                        x + factor * (dt * variance).sqrt() * shock(rng) + self.jump(rng, dt);
+                    let keep_going = visit(x, next, dt * variance * factor * factor);
+                    x = next;
+                    if !keep_going {
+                        break;
+                    }
+                    regime = regimes.draw(rng, &regimes.transition[regime]);
+                }
+            }
+            Dynamics::Har { ref har } => {
+                for (k, (dt, factor)) in steps.enumerate() {
+                    let variance = har.variance(k);
+                    let next =
+                        x + factor * (dt * variance).sqrt() * shock(rng) + self.jump(rng, dt);
+                    let keep_going = visit(x, next, dt * variance * factor * factor);
+                    x = next;
+                    if !keep_going {
+                        break;
+                    }
+                }
+            }
+        }
+        x
+    }
+
+    /// Log-return over `horizon` along one simulated path.
+    pub(crate) fn sample_log_return<R: Rng>(&self, rng: &mut R, horizon: Horizon) -> f64 {
+        self.walk(rng, horizon, |_, _, _| true)
+    }
+
+    /// Probability that the price ends strictly above `target_price`.
+    pub(crate) fn probability(
+        &self,
+        current_price: f64,
+        target_price: f64,
+        horizon: Horizon,
+        run: &Run,
+    ) -> SimulationResult {
+        let threshold = (target_price / current_price).ln();
+        let (hits, simulated) =
+            engine::count_hits(run, |rng| self.sample_log_return(rng, horizon) > threshold);
+        SimulationResult::from_hits(hits, simulated, run.seed, run.started.elapsed())
+    }
+
+    /// Exceedance probabilities for several strikes from one set of paths.
+    pub(crate) fn probability_ladder(
+        &self,
+        current_price: f64,
+        target_prices: &[f64],
+        horizon: Horizon,
+        run: &Run,
+    ) -> LadderResult {
+        let mut order: Vec<usize> = (0..target_prices.len()).collect();
+        order.sort_by(|&a, &b| target_prices[a].total_cmp(&target_prices[b]));
+        let thresholds: Vec<f64> = order
+            .iter()
+            .map(|&i| (target_prices[i] / current_price).ln())
+            .collect();
+
+        // buckets[j] counts paths that end above exactly the j lowest strikes.
+        let (buckets, simulated) = engine::fold_paths_until(
+            run,
+            || vec![0usize; thresholds.len() + 1],
+            |buckets, rng| {
+                let x = self.sample_log_return(rng, horizon);
+                buckets[thresholds.partition_point(|&t| t < x)] += 1;
+            },
+            |mut a, b| {
+                a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                a
+            },
+            |buckets, n| {
+                exceedances(buckets)
+                    .map(|hits| engine::hit_std_error(hits, n))
+                    .fold(0.0, f64::max)
+            },
+        );
+
+        let elapsed = run.started.elapsed();
+        let mut results = vec![None; target_prices.len()];
+        for (&i, hits) in order.iter().zip(exceedances(&buckets)) {
+            results[i] = Some(SimulationResult::from_hits(
+                hits, simulated, run.seed, elapsed,
+            ));
+        }
+        LadderResult::new(
+            target_prices.to_vec(),
+            results.into_iter().flatten().collect(),
+        )
+    }
+
+    /// Simulated terminal prices after `horizon`, one per path in
+    /// path order.
+    pub(crate) fn terminal_prices(
+        &self,
+        current_price: f64,
+        horizon: Horizon,
+        run: &Run,
+    ) -> Vec<f64> {
+        engine::fold_paths(
+            0..run.max_paths,
+            run.seed,
+            Vec::new,
+            |prices, rng| prices.push(current_price * self.sample_log_return(rng, horizon).exp()),
+            |mut a, b| {
+                a.extend(b);
+                a
+            },
+        )
+    }
+
+    /// Moments, quantiles and an optional `(bins, lo, hi)` histogram of the
+    /// terminal price. Without `lo`/`hi` the histogram spans all paths.
+    pub(crate) fn terminal_distribution(
+        &self,
+        current_price: f64,
+        horizon: Horizon,
+        quantile_levels: Vec<f64>,
+        histogram: Option<(usize, Option<f64>, Option<f64>)>,
+        run: &Run,
+    ) -> DistributionResult {
+        let mut prices = self.terminal_prices(current_price, horizon, run);
+        prices.par_sort_unstable_by(f64::total_cmp);
+
+        let moments = Moments::of(&prices);
+        let quantiles = quantile_levels
+            .iter()
+            .map(|&q| stats::quantile_sorted(&prices, q))
+            .collect();
+        let histogram = histogram.map(|(bins, lo, hi)| {
+            let lo = lo.unwrap_or(prices[0]);
+            let hi = hi.unwrap_or(prices[prices.len() - 1]);
+            stats::histogram(&prices, bins, lo, hi)
+        });
+
+        DistributionResult::new(
+            moments,
+            quantile_levels,
+            quantiles,
+            histogram,
+            prices.len(),
+            run.seed,
+            run.started.elapsed(),
+        )
+    }
+
+    /// Probability that the price touches `barrier_price` at some point
+    /// within `horizon`: an upper barrier if it lies above
+    /// `current_price`, a lower one otherwise.
+    ///
+    /// Between minute closes the log-price is treated as a Brownian bridge
+    /// with the step's variance, so each path contributes its conditional
+    /// touch probability rather than a 0/1 outcome.
+    pub(crate) fn barrier_probability(
+        &self,
+        current_price: f64,
+        barrier_price: f64,
+        horizon: Horizon,
+        run: &Run,
+    ) -> SimulationResult {
+        let barrier = (barrier_price / current_price).ln();
+        // Distance to the barrier in the direction of travel.
+        let sign = if barrier >= 0.0 { 1.0 } else { -1.0 };
+        let shock_variance = self.shock_variance();
+
+        let (tally, simulated) = engine::mean_until(run, |rng| {
+            if barrier == 0.0 {
+                return 1.0;
+            }
+            let mut miss = 1.0;
+            let mut touched = false;
+            self.walk(rng, horizon, |x0, x1, scale_sq| {
+                let d0 = sign * (barrier - x0);
+                let d1 = sign * (barrier - x1);
+                if d1 <= 0.0 {
+                    touched = true;
+                    return false;
+                }
+                let step_variance = scale_sq * shock_variance;
+                if step_variance > 0.0 {
+                    miss *= 1.0 - (-2.0 * d0 * d1 / step_variance).exp();
+                }
+                true
+            });
+            if touched {
+                1.0
+            } else {
+                1.0 - miss
+            }
+        });
+        SimulationResult::from_tally(&tally, simulated, run.seed, run.started.elapsed())
+    }
+}
+
+/// Paths above each sorted strike, from the per-bucket counts.
+fn exceedances(buckets: &[usize]) -> impl Iterator<Item = usize> + '_ {
+    (1..buckets.len()).map(move |j| buckets[j..].iter().sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::innovations;
+    use crate::variance::VarianceModel;
+
+    #[test]
+    fn bootstrap_compounds_log_returns() {
+        let model = Model::bootstrap(vec![0.5]).unwrap();
+        let horizon = Horizon::from_seconds(120.0).unwrap();
+        let mut rng = engine::path_rng(1, 0);
+        // exp(0.5 + 0.5) = e, where 1 + r would have compounded to 2.25.
+        assert_eq!(model.sample_log_return(&mut rng, horizon), 1.0);
+        let run = Run::new(100, Some(1), None, None).unwrap();
+        let result = model.probability(100.0, 100.0 * 0.9f64.exp(), horizon, &run);
+        assert_eq!(result.probability, 1.0);
+    }
+
+    #[test]
+    fn garch_feeds_back_the_scaled_residual() {
+        let (omega, alpha, beta) = (1e-7, 0.1, 0.8);
+        let garch = VarianceModel::Garch { omega, alpha, beta };
+        let model = Model::filtered(garch, vec![1.0], 0.0, 1e-6).unwrap();
+        let first = omega + beta * 1e-6;
+        // The second minute's variance sees the residual sqrt(first), not
+        // the unit shock, which would have added alpha to it.
+        let second = omega + (alpha + beta) * first;
+        let horizon = Horizon::from_seconds(120.0).unwrap();
+        let mut rng = engine::path_rng(1, 0);
+        let x = model.sample_log_return(&mut rng, horizon);
+        assert!((x - (first.sqrt() + second.sqrt())).abs() < 1e-15);
+    }
+
+    /// A bootstrap of centred Gaussian shocks with this standard deviation.
+    fn gaussian_bootstrap(sd: f64) -> Model<'static> {
+        let mut rng = engine::path_rng(3, 0);
+        let mut shocks: Vec<f64> = (0..100_000)
+            .map(|_| sd * innovations::standard_normal(&mut rng))
+            .collect();
+        let mean = Moments::of(&shocks).mean;
+        shocks.iter_mut().for_each(|z| *z -= mean);
+        Model::bootstrap(shocks).unwrap()
+    }
+
+    #[test]
+    fn barrier_matches_the_reflection_principle() {
+        let model = gaussian_bootstrap(1e-3);
+        let sd = Moments::of(&model.shocks).variance.sqrt();
+        let horizon = Horizon::from_seconds(3600.0).unwrap();
+        let run = Run::new(100_000, Some(4), None, None).unwrap();
+        for k in [-2.0, -0.5, 0.5, 1.0, 2.5] {
+            // Without drift P(max W > b) = 2 P(W_T > b), and symmetrically
+            // for the minimum.
+            let exact = 2.0 * (1.0 - innovations::normal_cdf(f64::abs(k)));
+            let barrier = 100.0 * (k * sd * 60f64.sqrt()).exp();
+            let result = model.barrier_probability(100.0, barrier, horizon, &run);
+            assert!(
+                (result.probability - exact).abs() < 4.0 * result.std_error,
+                "{k}: {} vs {exact}",
+                result.probability
+            );
+        }
+    }
+
+    #[test]
+    fn barrier_is_at_least_the_terminal_probability() {
+        let garch = VarianceModel::Garch {
+            omega: 1e-7,
+            alpha: 0.1,
+            beta: 0.85,
+        };
+        let mut rng = engine::path_rng(5, 0);
+        let residuals: Vec<f64> = (0..10_000)
+            .map(|_| innovations::standard_normal(&mut rng))
+            .collect();
+        let models = [
+            gaussian_bootstrap(1e-3),
+            Model::filtered(garch, residuals, 0.0, 1e-6).unwrap(),
+        ];
+        let horizon = Horizon::from_seconds(900.0).unwrap();
+        let run = Run::new(20_000, Some(6), None, None).unwrap();
+        for model in &models {
+            for target in [98.0, 99.5, 100.2, 101.0, 103.0] {
+                let barrier = model.barrier_probability(100.0, target, horizon, &run);
+                let mut terminal = model.probability(100.0, target, horizon, &run).probability;
+                if target < 100.0 {
+                    terminal = 1.0 - terminal;
+                }
+                // The same seeds give the same paths, and a path ending
+                // beyond the target touched it.
+                assert!(
+                    barrier.probability >= terminal,
+                    "{target}: {barrier:?} {terminal}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+// garch_monte_carlo/src/optimize.rs
+// Derivative-free minimization for the likelihood fits.
+
+/// Result of a minimization.
+#[derive(Clone, Debug)]
+pub(crate) struct Minimum {
+    pub x: Vec<f64>,
+    pub value: f64,
+    pub iterations: usize,
+    pub converged: bool,
+}
+
+/// Nelder-Mead simplex search for an unconstrained minimum of `f`, starting
+/// from a simplex with edge `step` around `start`. Non-finite values count
+/// as +inf, so constraints can be enforced by returning NaN.
+///
+/// Converges once the simplex values agree to `tolerance` (relative) and its
+/// vertices to `tolerance` (absolute, in the parameters).
+pub(crate) fn nelder_mead<F>(
+    f: F,
+    start: &[f64],
+    step: f64,
+    tolerance: f64,
+    max_iterations: usize,
+) -> Minimum
+where
+    F: Fn(&[f64]) -> f64,
+{
+    let eval = |x: &[f64]| {
+        let v = f(x);
+        if v.is_finite() {
+            v
+        } else {
+            f64::INFINITY
+        }
+    };
+    let dim = start.len();
+    let mut simplex: Vec<(Vec<f64>, f64)> = (0..=dim)
+        .map(|i| {
+            let mut x = start.to_vec();
+            if i > 0 {
+                x[i - 1] += step;
+            }
+            let v = eval(&x);
+            (x, v)
+        })
+        .collect();
+
+    let mut iterations = 0;
+    let mut converged = false;
+    while iterations < max_iterations {
+        simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let (best, worst) = (simplex[0].1, simplex[dim].1);
+        let spread = simplex
+            .iter()
+            .flat_map(|(x, _)| x.iter().zip(&simplex[0].0).map(|(a, b)| (a - b).abs()))
+            .fold(0.0, f64::max);
+        if (worst - best).abs() <= tolerance * (best.abs() + tolerance) && spread <= tolerance {
+            converged = true;
+            break;
+        }
+        iterations += 1;
+
+        let centroid: Vec<f64> = (0..dim)
+            .map(|j| simplex[..dim].iter().map(|(x, _)| x[j]).sum::<f64>() / dim as f64)
+            .collect();
+        let towards = |t: f64| -> Vec<f64> {
+            centroid
+                .iter()
+                .zip(&simplex[dim].0)
+                .map(|(c, w)| c + t * (w - c))
+                .collect()
+        };
+
+        let reflected = towards(-1.0);
+        let reflected_value = eval(&reflected);
+        if reflected_value < best {
+            let expanded = towards(-2.0);
+            let expanded_value = eval(&expanded);
+            simplex[dim] = if expanded_value < reflected_value {
+                (expanded, expanded_value)
+            } else {
+                (reflected, reflected_value)
+            };
+        } else if reflected_value < simplex[dim - 1].1 {
+            simplex[dim] = (reflected, reflected_value);
+        } else {
+            let (contracted, contracted_value) = if reflected_value < worst {
+                let x = towards(-0.5);
+                let v = eval(&x);
+                (x, v)
+            } else {
+                let x = towards(0.5);
+                let v = eval(&x);
+                (x, v)
+            };
+            if contracted_value < reflected_value.min(worst) {
+                simplex[dim] = (contracted, contracted_value);
+            } else {
+                // Shrink everything towards the best vertex.
+                let best_x = simplex[0].0.clone();
+                for (x, v) in simplex.iter_mut().skip(1) {
+                    for (xi, bi) in x.iter_mut().zip(&best_x) {
+                        *xi = bi + 0.5 * (*xi - bi);
+                    }
+                    *v = eval(x);
+                }
+            }
+        }
+    }
+
+    simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+    let (x, value) = simplex.swap_remove(0);
+    Minimum {
+        x,
+        value,
+        iterations,
+        converged,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rosenbrock(x: &[f64]) -> f64 {
+        (1.0 - x[0]).powi(2) + 100.0 * (x[1] - x[0] * x[0]).powi(2)
+    }
+
+    #[test]
+    fn finds_the_rosenbrock_minimum() {
+        let min = nelder_mead(rosenbrock, &[-1.2, 1.0], 0.5, 1e-10, 5000);
+        assert!(min.converged);
+        assert!((min.x[0] - 1.0).abs() < 1e-4 && (min.x[1] - 1.0).abs() < 1e-4);
+        assert!(min.value < 1e-8);
+    }
+
+    #[test]
+    fn treats_nan_as_a_constraint() {
+        // Minimum of x^2 subject to x >= 1.
+        let f = |x: &[f64]| if x[0] < 1.0 { f64::NAN } else { x[0] * x[0] };
+        let min = nelder_mead(f, &[3.0], 0.5, 1e-10, 1000);
+        assert!((min.x[0] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn reports_running_out_of_iterations() {
+        let min = nelder_mead(rosenbrock, &[-1.2, 1.0], 0.5, 1e-10, 10);
+        assert!(!min.converged);
+        assert_eq!(min.iterations, 10);
+        assert!(min.value < rosenbrock(&[-1.2, 1.0]));
+    }
+}
@@ -0,0 +1,255 @@
+// garch_monte_carlo/src/qmc.rs
+// Randomized quasi-Monte Carlo: Owen-scrambled Sobol points as shock uniforms.
+
+use crate::block::Blocks;
+use crate::engine;
+use crate::innovations::{Innovations, InverseCdf};
+use crate::model::{Horizon, Model};
+use crate::result::SimulationResult;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::prelude::*;
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// Bits of precision of a Sobol coordinate, and the most points a scramble
+/// can have (2^BITS).
+const BITS: usize = 32;
+
+/// Joe and Kuo's (2008) direction numbers `new-joe-kuo-6.21201`, cut to
+/// their first 2048 dimensions: one row `d s a m_1 .. m_s` per dimension
+/// after the first, with `s` the degree of its primitive polynomial, `a`
+/// the polynomial's inner coefficients and `m_k` the initial direction
+/// numbers. They were searched for good two-dimensional projections, which
+/// is where scrambled points still gain over plain Monte Carlo at long
+/// horizons.
+const JOE_KUO: &str = include_str!("../data/new-joe-kuo-6.2048");
+
+/// Sobol sequence (base 2 digital net) generator matrices, one per
+/// dimension: van der Corput's first, then Joe and Kuo's.
+pub(crate) struct Sobol {
+    /// `directions[d][k]` is the dimension-`d` coordinate of point `2^k`.
+    directions: &'static [[u32; BITS]],
+}
+
+impl Sobol {
+    pub(crate) fn new(dimensions: usize) -> PyResult<Self> {
+        static DIRECTIONS: OnceLock<Vec<[u32; BITS]>> = OnceLock::new();
+        let directions = DIRECTIONS.get_or_init(joe_kuo_directions);
+        if dimensions > directions.len() {
+            return Err(PyValueError::new_err(format!(
+                "quasi-Monte Carlo supports at most {} simulated steps",
+                directions.len()
+            )));
+        }
+        Ok(Sobol {
+            directions: &directions[..dimensions],
+        })
+    }
+
+    /// Coordinate `dimension` of point `index`, as a 32-bit fraction.
+    pub(crate) fn coordinate(&self, index: u32, dimension: usize) -> u32 {
+        let directions = &self.directions[dimension];
+        let mut bits = index;
+        let mut x = 0;
+        while bits != 0 {
+            x ^= directions[bits.trailing_zeros() as usize];
+            bits &= bits - 1;
+        }
+        x
+    }
+}
+
+/// Generator matrices of every dimension in `JOE_KUO`, after van der
+/// Corput's.
+fn joe_kuo_directions() -> Vec<[u32; BITS]> {
+    let mut directions = vec![std::array::from_fn(|k| 1u32 << (BITS - 1 - k))];
+    for row in JOE_KUO.lines().skip(1) {
+        let fields: Vec<u32> = row
+            .split_whitespace()
+            .map(|field| field.parse().expect("malformed direction numbers"))
+            .collect();
+        let (s, a) = (fields[1] as usize, fields[2]);
+        // m[k] < 2^(k+1) and odd; the first `s` are given, the rest follow
+        // the polynomial's recurrence.
+        let mut m = [0u32; BITS];
+        m[..s].copy_from_slice(&fields[3..3 + s]);
+        for k in s..BITS {
+            let mut value = m[k - s] ^ (m[k - s] << s);
+            for j in 1..s {
+                if (a >> (s - 1 - j)) & 1 == 1 {
+                    value ^= m[k - j] << j;
+                }
+            }
+            m[k] = value;
+        }
+        directions.push(std::array::from_fn(|k| m[k] << (BITS - 1 - k)));
+    }
+    directions
+}
+
+/// Owen (nested uniform) scrambling of a 32-bit fraction by Burley's (2020)
+/// hash: with the bits reversed, every output bit depends only on the seed
+/// and the same and more significant input bits.
+fn owen_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits().wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x.reverse_bits()
+}
+
+impl<'a> Model<'a> {
+    /// `probability` by randomized quasi-Monte Carlo: path `i` takes its
+    /// step-`k` shock from coordinate `k` of Sobol point `i`, Owen-scrambled
+    /// and mapped through the innovations' inverse CDF. `scrambles`
+    /// independent scramblings of `2^m` points each (`num_simulations`
+    /// shared out, rounded up to a power of two) give an unbiased mean and
+    /// its standard error. Other randomness (jumps, Heston variance, regime
+    /// switches) stays pseudo-random. Needs i.i.d. resampling.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn probability_qmc(
+        &self,
+        current_price: f64,
+        target_price: f64,
+        horizon: Horizon,
+        num_simulations: usize,
+        scrambles: usize,
+        seed: u64,
+        started: Instant,
+    ) -> PyResult<SimulationResult> {
+        if scrambles < 2 {
+            return Err(PyValueError::new_err(
+                "need at least 2 scrambles to estimate the error",
+            ));
+        }
+        if matches!(self.innovations, Innovations::Bootstrap) && !matches!(self.blocks, Blocks::Iid)
+        {
+            return Err(PyValueError::new_err(
+                "quasi-Monte Carlo needs i.i.d. resampling (block 'iid')",
+            ));
+        }
+        let inverse = InverseCdf::new(self.innovations, &self.shocks);
+        let points = num_simulations
+            .div_ceil(scrambles)
+            .max(1)
+            .next_power_of_two()
+            .min(1 << BITS);
+        let threshold = (target_price / current_price).ln();
+        let dimensions = horizon.steps().count();
+        let sobol = Sobol::new(dimensions)?;
+
+        let estimates: Vec<f64> = (0..scrambles)
+            .map(|r| {
+                let mut scramble_rng = engine::path_rng(!seed, r as u64);
+                let seeds: Vec<u32> = (0..dimensions).map(|_| scramble_rng.gen()).collect();
+                let first = r * points;
+                let hits = engine::fold_indexed(
+                    first..first + points,
+                    seed,
+                    || 0usize,
+                    |hits, i, rng| {
+                        let index = (i - first) as u32;
+                        let mut k = 0;
+                        let x = self.walk_shocks(
+                            rng,
+                            horizon,
+                            |rng| {
+                                let digits =
+                                    owen_scramble(sobol.coordinate(index, k), seeds[k]) as f64;
+                                k += 1;
+                                // Uniform within the finest cell, so each
+                                // coordinate is exactly uniform on (0, 1).
+                                let jitter =
+                                    ((rng.gen::<u64>() >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+                                inverse.shock((digits + jitter) / (1u64 << BITS) as f64)
+                            },
+                            |_, _, _| true,
+                        );
+                        *hits += (x > threshold) as usize;
+                    },
+                    |a, b| a + b,
+                );
+                hits as f64 / points as f64
+            })
+            .collect();
+
+        let r = scrambles as f64;
+        let p = estimates.iter().sum::<f64>() / r;
+        let variance = estimates.iter().map(|e| (e - p).powi(2)).sum::<f64>() / (r - 1.0) / r;
+        let paths = scrambles * points;
+        let factor = p * (1.0 - p) / paths as f64 / variance;
+        Ok(SimulationResult::from_estimate(
+            p,
+            variance.sqrt(),
+            paths,
+            seed,
+            started.elapsed(),
+            factor,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_points_match_joe_kuo() {
+        // The first eight points of the first six dimensions, in the
+        // Gray-code order of Joe and Kuo's reference generator.
+        let expected = [
+            [0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            [0.5, 0.5, 0.5, 0.5, 0.5, 0.5],
+            [0.75, 0.25, 0.25, 0.25, 0.75, 0.75],
+            [0.25, 0.75, 0.75, 0.75, 0.25, 0.25],
+            [0.375, 0.375, 0.625, 0.875, 0.375, 0.125],
+            [0.875, 0.875, 0.125, 0.375, 0.875, 0.625],
+            [0.625, 0.125, 0.875, 0.625, 0.625, 0.875],
+            [0.125, 0.625, 0.375, 0.125, 0.125, 0.375],
+        ];
+        let sobol = Sobol::new(6).unwrap();
+        for (i, point) in expected.iter().enumerate() {
+            let gray = (i ^ (i >> 1)) as u32;
+            for (d, &x) in point.iter().enumerate() {
+                let coordinate = sobol.coordinate(gray, d) as f64 / (1u64 << BITS) as f64;
+                assert_eq!(coordinate, x, "point {i}, dimension {d}");
+            }
+        }
+    }
+
+    #[test]
+    fn scrambled_points_stay_stratified_in_every_dimension() {
+        let m = 10;
+        let sobol = Sobol::new(2048).unwrap();
+        let mut rng = engine::path_rng(3, 0);
+        for d in (0..64).chain(2000..2048) {
+            let seed = rng.gen();
+            // Each of the 2^m intervals of width 2^-m holds exactly one point.
+            let mut cells: Vec<u32> = (0..1u32 << m)
+                .map(|i| owen_scramble(sobol.coordinate(i, d), seed) >> (BITS - m))
+                .collect();
+            cells.sort_unstable();
+            assert!(cells.iter().copied().eq(0..1u32 << m), "dimension {d}");
+        }
+    }
+
+    #[test]
+    fn horizons_beyond_the_table_are_refused() {
+        assert!(Sobol::new(2048).is_ok());
+        assert!(Sobol::new(2049).is_err());
+        let model = Model::bootstrap(vec![-1e-3, 1e-3]).unwrap();
+        let run = |minutes: usize| {
+            let horizon = Horizon::from_seconds(60.0 * minutes as f64).unwrap();
+            model.probability_qmc(100.0, 100.0, horizon, 4, 2, 1, Instant::now())
+        };
+        assert!(run(2048).is_ok());
+        assert!(run(2049).is_err());
+        // A partial minute is a step of its own.
+        let horizon = Horizon::from_seconds(60.0 * 2048.0 + 30.0).unwrap();
+        assert!(model
+            .probability_qmc(100.0, 100.0, horizon, 4, 2, 1, Instant::now())
+            .is_err());
+    }
+}
@@ -0,0 +1,256 @@
+// garch_monte_carlo/src/reduction.rs
+// Antithetic pairs, a lognormal control variate and importance sampling for
+// exceedance probabilities.
+
+use crate::block::Blocks;
+use crate::engine::{self, Run};
+use crate::innovations::{self, Innovations, InverseCdf};
+use crate::model::{Dynamics, Horizon, Model};
+use crate::result::SimulationResult;
+use crate::stats::{Moments, PairTally};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::prelude::*;
+
+/// Variance-reduction techniques `Model::probability_with` can combine.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Reduction {
+    /// Simulate paths in pairs, the second from the mirror image of the
+    /// first one's shocks.
+    pub antithetic: bool,
+    /// Regress out a Gaussian log-return built from the same shocks, whose
+    /// exceedance probability is a lognormal digital known in closed form.
+    pub control_variate: bool,
+    /// Draw shocks tilted toward the strike and reweight by likelihood
+    /// ratios, for probabilities near 0 or 1. Used on its own.
+    pub importance: bool,
+}
+
+/// One shock and a standard normal that moves with it: both are
+/// quantiles of the same uniform, so they are comonotone whatever the
+/// innovations. `mirrored` reflects the uniform to give the antithetic
+/// shock of the same random numbers.
+fn draw_coupled<R: Rng>(rng: &mut R, inverse: &InverseCdf, mirrored: bool) -> (f64, f64) {
+    // Uniform on (0, 1), symmetric under reflection.
+    let u = ((rng.gen::<u64>() >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+    let u = if mirrored { 1.0 - u } else { u };
+    (inverse.shock(u), innovations::normal_quantile(u))
+}
+
+/// Inputs of the control variate: the Gaussian log-return `drift + sd *
+/// sum(weights[k] * g[k])` over the steps, with `g` the standard normals
+/// coupled to the shocks, and its exceedance probability.
+struct Control {
+    weights: Vec<f64>,
+    drift: f64,
+    sd: f64,
+    expected: f64,
+}
+
+impl<'a> Model<'a> {
+    /// What each step's shock is multiplied by when volatility stays at
+    /// its current level: a deterministic stand-in for the path's scales.
+    pub(crate) fn frozen_scales(&self, horizon: Horizon) -> Vec<f64> {
+        let steps = horizon.seasonal_steps(self.seasonality.as_deref());
+        steps
+            .enumerate()
+            .map(|(k, (dt, factor))| {
+                let variance = match self.dynamics {
+                    Dynamics::Bootstrap => 1.0,
+                    Dynamics::Filtered { sigma_sq, .. } => sigma_sq,
+                    Dynamics::Heston { v, .. } => v.max(0.0),
+                    Dynamics::Regime { regimes, probs } => regimes.variance(&probs),
+                    Dynamics::Har { ref har } => har.variance(k),
+                };
+                factor * (dt * variance).sqrt()
+            })
+            .collect()
+    }
+
+    /// Log-return over `horizon` from coupled draws, and the control's
+    /// Gaussian log-return from the same draws.
+    fn walk_coupled<R: Rng>(
+        &self,
+        rng: &mut R,
+        horizon: Horizon,
+        inverse: &InverseCdf,
+        control: &Control,
+        mirrored: bool,
+    ) -> (f64, f64) {
+        let mut step = 0;
+        let mut weighted = 0.0;
+        let x = self.walk_shocks(
+            rng,
+            horizon,
+            |rng| {
+                let (shock, normal) = draw_coupled(rng, inverse, mirrored);
+                weighted += control.weights[step] * normal;
+                step += 1;
+                shock
+            },
+            |_, _, _| true,
+        );
+        (x, control.drift + control.sd * weighted)
+    }
+
+    /// `probability` with antithetic pairs and/or the lognormal control
+    /// variate, or importance sampling, reporting the variance-reduction
+    /// factor. These need i.i.d. draws, so block bootstraps are refused.
+    pub(crate) fn probability_with(
+        &self,
+        current_price: f64,
+        target_price: f64,
+        horizon: Horizon,
+        reduction: Reduction,
+        run: &Run,
+    ) -> PyResult<SimulationResult> {
+        if !reduction.antithetic && !reduction.control_variate && !reduction.importance {
+            return Ok(self.probability(current_price, target_price, horizon, run));
+        }
+        if matches!(self.innovations, Innovations::Bootstrap) && !matches!(self.blocks, Blocks::Iid)
+        {
+            return Err(PyValueError::new_err(
+                "variance reduction needs i.i.d. resampling (block 'iid')",
+            ));
+        }
+        if reduction.importance {
+            if reduction.antithetic || reduction.control_variate {
+                return Err(PyValueError::new_err(
+                    "importance sampling does not combine with antithetic or control variates",
+                ));
+            }
+            return Ok(self.probability_tilted(current_price, target_price, horizon, run));
+        }
+        let threshold = (target_price / current_price).ln();
+
+        let inverse = InverseCdf::new(self.innovations, &self.shocks);
+        let (mean, sd) = match inverse {
+            InverseCdf::Empirical(ref sorted) => {
+                let moments = Moments::of(sorted);
+                (moments.mean, moments.variance.sqrt())
+            }
+            InverseCdf::Parametric { loc, scale, .. } => (loc, scale),
+        };
+        let weights = self.frozen_scales(horizon);
+        let drift = mean * weights.iter().sum::<f64>();
+        let spread = sd * weights.iter().map(|w| w * w).sum::<f64>().sqrt();
+        let expected = if spread > 0.0 {
+            innovations::normal_cdf((drift - threshold) / spread)
+        } else {
+            (drift > threshold) as u8 as f64
+        };
+        let control = Control {
+            weights,
+            drift,
+            sd,
+            expected,
+        };
+
+        let per_sample = if reduction.antithetic { 2 } else { 1 };
+        let samples = Run {
+            max_paths: run.max_paths.div_ceil(per_sample),
+            ..*run
+        };
+        let estimate = |tally: &PairTally| {
+            let (mean_y, mean_c, var_y, var_c, cov) = tally.moments();
+            let n = tally.n as f64;
+            if reduction.control_variate && var_c > 0.0 {
+                let beta = cov / var_c;
+                let p = mean_y - beta * (mean_c - control.expected);
+                (p, (var_y - cov * cov / var_c).max(0.0) / n)
+            } else {
+                (mean_y, var_y / n)
+            }
+        };
+        let (tally, simulated) = engine::fold_paths_until(
+            &samples,
+            PairTally::default,
+            |tally, rng| {
+                let mut mirror_rng = rng.clone();
+                let (x, l) = self.walk_coupled(rng, horizon, &inverse, &control, false);
+                let mut y = (x > threshold) as u8 as f64;
+                let mut c = (l > threshold) as u8 as f64;
+                if reduction.antithetic {
+                    let (x, l) =
+                        self.walk_coupled(&mut mirror_rng, horizon, &inverse, &control, true);
+                    y = 0.5 * (y + (x > threshold) as u8 as f64);
+                    c = 0.5 * (c + (l > threshold) as u8 as f64);
+                }
+                tally.push(y, c);
+            },
+            PairTally::merge,
+            |tally, _| estimate(tally).1.sqrt(),
+        );
+
+        let (p, variance) = estimate(&tally);
+        let p = p.clamp(0.0, 1.0);
+        let paths = simulated * per_sample;
+        let factor = p * (1.0 - p) / paths as f64 / variance;
+        Ok(SimulationResult::from_estimate(
+            p,
+            variance.sqrt(),
+            paths,
+            run.seed,
+            run.started.elapsed(),
+            factor,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Skewed 1-minute returns to bootstrap.
+    fn model() -> Model<'static> {
+        let mut rng = engine::path_rng(9, 0);
+        let returns: Vec<f64> = (0..5000)
+            .map(|_| {
+                let z = innovations::standard_normal(&mut rng);
+                1e-3 * (z + 0.2 * (z * z - 1.0))
+            })
+            .collect();
+        Model::bootstrap(returns).unwrap()
+    }
+
+    fn estimate(target: f64, antithetic: bool, control_variate: bool) -> SimulationResult {
+        let reduction = Reduction {
+            antithetic,
+            control_variate,
+            importance: false,
+        };
+        let run = Run::new(100_000, Some(11), None, None).unwrap();
+        let horizon = Horizon::from_seconds(900.0).unwrap();
+        model()
+            .probability_with(100.0, target, horizon, reduction, &run)
+            .unwrap()
+    }
+
+    #[test]
+    fn reduced_estimates_agree_with_the_plain_one() {
+        for target in [99.7, 100.0, 100.4] {
+            let plain = estimate(target, false, false);
+            for (antithetic, control_variate) in [(true, false), (false, true), (true, true)] {
+                let reduced = estimate(target, antithetic, control_variate);
+                let combined = plain.std_error.hypot(reduced.std_error);
+                let gap = (reduced.probability - plain.probability).abs();
+                assert!(
+                    gap < 3.0 * combined,
+                    "target {target}, antithetic {antithetic}, control {control_variate}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn at_the_money_variance_shrinks() {
+        for (antithetic, control_variate) in [(true, false), (false, true), (true, true)] {
+            let result = estimate(100.0, antithetic, control_variate);
+            let factor = result.variance_reduction.unwrap();
+            assert!(
+                factor > 1.0,
+                "antithetic {antithetic}, control {control_variate}: {factor}"
+            );
+        }
+    }
+}
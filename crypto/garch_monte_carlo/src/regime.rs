@@ -0,0 +1,411 @@
+// garch_monte_carlo/src/regime.rs
+// Markov regime-switching volatility: Hamilton filter and EM fit.
+
+use crate::array;
+use crate::model::Model;
+use crate::simulator::Simulator;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::prelude::*;
+use std::f64::consts::PI;
+
+/// Most regimes a model can have.
+pub(crate) const MAX_REGIMES: usize = 3;
+
+/// Zero-mean 1-minute returns whose variance depends on a hidden Markov
+/// regime. Only the first `k` entries of each array are used.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Regimes {
+    pub k: usize,
+    pub variances: [f64; MAX_REGIMES],
+    /// `transition[i][j]` is P(next regime j | regime i).
+    pub transition: [[f64; MAX_REGIMES]; MAX_REGIMES],
+}
+
+impl Regimes {
+    pub(crate) fn new(variances: &[f64], transition: &[Vec<f64>]) -> PyResult<Self> {
+        let k = variances.len();
+        let valid_row = |row: &Vec<f64>| {
+            row.len() == k
+                && row.iter().all(|&p| (0.0..=1.0).contains(&p))
+                && (row.iter().sum::<f64>() - 1.0).abs() < 1e-9
+        };
+        if !(1..=MAX_REGIMES).contains(&k)
+            || variances.iter().any(|v| v.is_nan() || *v <= 0.0)
+            || transition.len() != k
+            || !transition.iter().all(valid_row)
+        {
+            return Err(PyValueError::new_err(
+                "need 1 to 3 positive variances and a matching transition matrix whose rows sum to one",
+            ));
+        }
+        let mut regimes = Regimes {
+            k,
+            variances: [0.0; MAX_REGIMES],
+            transition: [[0.0; MAX_REGIMES]; MAX_REGIMES],
+        };
+        regimes.variances[..k].copy_from_slice(variances);
+        for (row, given) in regimes.transition.iter_mut().zip(transition) {
+            row[..k].copy_from_slice(given);
+        }
+        Ok(regimes)
+    }
+
+    /// Regime probabilities one minute after `probs`.
+    pub(crate) fn predict(&self, probs: &[f64; MAX_REGIMES]) -> [f64; MAX_REGIMES] {
+        let mut next = [0.0; MAX_REGIMES];
+        for (i, p) in probs.iter().enumerate().take(self.k) {
+            for (j, n) in next.iter_mut().enumerate().take(self.k) {
+                *n += p * self.transition[i][j];
+            }
+        }
+        next
+    }
+
+    /// Hamilton filter step: regime probabilities after observing `r`
+    /// under the predicted `probs`, and the density of `r`.
+    pub(crate) fn filter(&self, probs: &[f64; MAX_REGIMES], r: f64) -> ([f64; MAX_REGIMES], f64) {
+        let mut posterior = [0.0; MAX_REGIMES];
+        for j in 0..self.k {
+            posterior[j] = probs[j] * normal_density(r, self.variances[j]);
+        }
+        let density: f64 = posterior.iter().sum();
+        if density > 0.0 {
+            posterior.iter_mut().for_each(|p| *p /= density);
+        } else {
+            posterior = *probs;
+        }
+        (posterior, density)
+    }
+
+    /// Expected variance under regime probabilities `probs`.
+    pub(crate) fn variance(&self, probs: &[f64; MAX_REGIMES]) -> f64 {
+        (0..self.k).map(|j| probs[j] * self.variances[j]).sum()
+    }
+
+    /// Draws a regime from `probs`.
+    pub(crate) fn draw<R: Rng>(&self, rng: &mut R, probs: &[f64; MAX_REGIMES]) -> usize {
+        let u: f64 = rng.gen();
+        let mut cumulative = 0.0;
+        for (j, p) in probs.iter().enumerate().take(self.k) {
+            cumulative += p;
+            if u < cumulative {
+                return j;
+            }
+        }
+        self.k - 1
+    }
+}
+
+fn normal_density(r: f64, variance: f64) -> f64 {
+    (-0.5 * r * r / variance).exp() / (2.0 * PI * variance).sqrt()
+}
+
+/// Hamilton filter over `returns` from the regime probabilities `initial`
+/// of the first, writing each posterior to `filtered`. Returns the
+/// log-likelihood.
+fn hamilton(
+    regimes: &Regimes,
+    initial: [f64; MAX_REGIMES],
+    returns: &[f64],
+    filtered: &mut [[f64; MAX_REGIMES]],
+) -> f64 {
+    let mut ll = 0.0;
+    let mut predicted = initial;
+    for (t, &r) in returns.iter().enumerate() {
+        let (posterior, density) = regimes.filter(&predicted, r);
+        ll += density.max(f64::MIN_POSITIVE).ln();
+        filtered[t] = posterior;
+        predicted = regimes.predict(&posterior);
+    }
+    ll
+}
+
+/// Result of fitting `Regimes` by EM, with the filtered regime
+/// probabilities at the end of the sample.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct RegimeFit {
+    regimes: Regimes,
+    /// P(regime | returns so far) at the last return.
+    filtered: [f64; MAX_REGIMES],
+    #[pyo3(get)]
+    pub log_likelihood: f64,
+    #[pyo3(get)]
+    pub converged: bool,
+    #[pyo3(get)]
+    pub iterations: usize,
+    std_residuals: Vec<f64>,
+}
+
+#[pymethods]
+impl RegimeFit {
+    #[getter]
+    fn num_regimes(&self) -> usize {
+        self.regimes.k
+    }
+
+    /// Variance of a 1-minute return in each regime, calmest first.
+    #[getter]
+    fn variances(&self, py: Python<'_>) -> PyResult<PyObject> {
+        array::to_numpy(py, self.regimes.variances[..self.regimes.k].to_vec())
+    }
+
+    /// Rows of P(next regime | regime).
+    #[getter]
+    fn transition(&self) -> Vec<Vec<f64>> {
+        let k = self.regimes.k;
+        self.regimes.transition[..k]
+            .iter()
+            .map(|row| row[..k].to_vec())
+            .collect()
+    }
+
+    /// Expected minutes spent in each regime per visit.
+    #[getter]
+    fn expected_durations(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let k = self.regimes.k;
+        let durations = (0..k)
+            .map(|j| 1.0 / (1.0 - self.regimes.transition[j][j]))
+            .collect();
+        array::to_numpy(py, durations)
+    }
+
+    /// Hamilton-filtered probability of each regime at the last return.
+    #[getter]
+    fn filtered_probabilities(&self, py: Python<'_>) -> PyResult<PyObject> {
+        array::to_numpy(py, self.filtered[..self.regimes.k].to_vec())
+    }
+
+    /// Returns over their smoothed regime volatility, as a NumPy array.
+    #[getter]
+    fn std_residuals(&self, py: Python<'_>) -> PyResult<PyObject> {
+        array::to_numpy(py, self.std_residuals.clone())
+    }
+
+    /// A regime-switching `Simulator` starting from the regime
+    /// probabilities of the minute after the last return.
+    #[pyo3(signature = (window=None))]
+    fn simulator(&self, window: Option<usize>) -> PyResult<Simulator> {
+        let probs = self.regimes.predict(&self.filtered);
+        let mut model = Model::regimes(self.regimes, self.std_residuals.clone(), probs)?;
+        model.window = window;
+        Ok(Simulator::new(model))
+    }
+
+    fn __repr__(&self) -> String {
+        let k = self.regimes.k;
+        format!(
+            "RegimeFit(num_regimes={}, variances={:?}, filtered_probabilities={:.4?}, log_likelihood={:.3}, converged={}, iterations={})",
+            k,
+            &self.regimes.variances[..k],
+            &self.filtered[..k],
+            self.log_likelihood,
+            self.converged,
+            self.iterations
+        )
+    }
+}
+
+/// Fits `k` zero-mean regimes to `returns` by EM (Baum-Welch): a scaled
+/// Hamilton filter forward, Kim smoother backward, then closed-form
+/// updates of the variances and transition probabilities. Starts from
+/// variances of equal-count groups of squared returns and sticky
+/// transitions, and stops when the log-likelihood improves by less than
+/// `1e-9` relative.
+pub(crate) fn fit_regimes(returns: &[f64], k: usize, max_iterations: usize) -> PyResult<RegimeFit> {
+    if !(1..=MAX_REGIMES).contains(&k) {
+        return Err(PyValueError::new_err("num_regimes must be 1, 2 or 3"));
+    }
+    let n = returns.len();
+    if n < 10 * k || returns.iter().any(|r| !r.is_finite()) {
+        return Err(PyValueError::new_err(
+            "need at least ten finite returns per regime to fit",
+        ));
+    }
+
+    let mut squares: Vec<f64> = returns.iter().map(|r| r * r).collect();
+    squares.sort_unstable_by(f64::total_cmp);
+    let floor = squares.iter().sum::<f64>() / n as f64 * 1e-6;
+    if floor <= 0.0 {
+        return Err(PyValueError::new_err("returns are all zero"));
+    }
+    let mut variances = vec![0.0; k];
+    for (j, chunk) in squares.chunks(n.div_ceil(k)).enumerate() {
+        variances[j] = (chunk.iter().sum::<f64>() / chunk.len() as f64).max(floor);
+    }
+    let stay = if k == 1 { 1.0 } else { 0.98 };
+    let transition: Vec<Vec<f64>> = (0..k)
+        .map(|i| {
+            (0..k)
+                .map(|j| {
+                    if i == j {
+                        stay
+                    } else {
+                        (1.0 - stay) / (k - 1) as f64
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    let mut regimes = Regimes::new(&variances, &transition)?;
+    let mut initial = [0.0; MAX_REGIMES];
+    initial[..k].fill(1.0 / k as f64);
+
+    let mut filtered = vec![[0.0; MAX_REGIMES]; n];
+    let mut smoothed = vec![[0.0; MAX_REGIMES]; n];
+    let mut log_likelihood = f64::NEG_INFINITY;
+    let mut converged = false;
+    let mut iterations = 0;
+    while iterations < max_iterations {
+        iterations += 1;
+
+        // Forward: Hamilton filter.
+        let ll = hamilton(&regimes, initial, returns, &mut filtered);
+
+        // Backward: Kim smoother, accumulating expected transitions.
+        let mut counts = [[0.0; MAX_REGIMES]; MAX_REGIMES];
+        smoothed[n - 1] = filtered[n - 1];
+        for t in (0..n - 1).rev() {
+            let next_predicted = regimes.predict(&filtered[t]);
+            let mut current = [0.0; MAX_REGIMES];
+            for i in 0..k {
+                for j in 0..k {
+                    if next_predicted[j] <= 0.0 {
+                        continue;
+                    }
+                    let joint = filtered[t][i] * regimes.transition[i][j] * smoothed[t + 1][j]
+                        / next_predicted[j];
+                    current[i] += joint;
+                    counts[i][j] += joint;
+                }
+            }
+            smoothed[t] = current;
+        }
+
+        // M-step.
+        for j in 0..k {
+            let weight: f64 = smoothed.iter().map(|p| p[j]).sum();
+            let weighted: f64 = smoothed
+                .iter()
+                .zip(returns)
+                .map(|(p, r)| p[j] * r * r)
+                .sum();
+            regimes.variances[j] = if weight > 0.0 {
+                (weighted / weight).max(floor)
+            } else {
+                regimes.variances[j]
+            };
+            let row_total: f64 = counts[j][..k].iter().sum();
+            if row_total > 0.0 {
+                for (p, count) in regimes.transition[j].iter_mut().zip(&counts[j][..k]) {
+                    *p = count / row_total;
+                }
+            }
+        }
+        initial = smoothed[0];
+
+        let improvement = ll - log_likelihood;
+        log_likelihood = ll;
+        if improvement.abs() <= 1e-9 * ll.abs() {
+            converged = true;
+            break;
+        }
+    }
+    // The last E-step saw the parameters before the final M-step; filter
+    // once more so the likelihood and probabilities reported match those
+    // returned.
+    log_likelihood = hamilton(&regimes, initial, returns, &mut filtered);
+
+    // Calmest regime first.
+    let mut order: Vec<usize> = (0..k).collect();
+    order.sort_by(|&a, &b| regimes.variances[a].total_cmp(&regimes.variances[b]));
+    let permute = |probs: &[f64; MAX_REGIMES]| {
+        let mut out = [0.0; MAX_REGIMES];
+        for (new, &old) in order.iter().enumerate() {
+            out[new] = probs[old];
+        }
+        out
+    };
+    let mut sorted = regimes;
+    for (new, &old) in order.iter().enumerate() {
+        sorted.variances[new] = regimes.variances[old];
+        for (new_j, &old_j) in order.iter().enumerate() {
+            sorted.transition[new][new_j] = regimes.transition[old][old_j];
+        }
+    }
+
+    let std_residuals = returns
+        .iter()
+        .zip(&smoothed)
+        .map(|(r, p)| r / regimes.variance(p).sqrt())
+        .collect();
+    Ok(RegimeFit {
+        regimes: sorted,
+        filtered: permute(&filtered[n - 1]),
+        log_likelihood,
+        converged,
+        iterations,
+        std_residuals,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine;
+    use crate::innovations;
+
+    /// `n` returns of `regimes`, starting in the calm regime.
+    fn simulate(regimes: &Regimes, n: usize, seed: u64) -> Vec<f64> {
+        let mut rng = engine::path_rng(seed, 0);
+        let mut regime = 0;
+        (0..n)
+            .map(|_| {
+                let r = regimes.variances[regime].sqrt() * innovations::standard_normal(&mut rng);
+                regime = regimes.draw(&mut rng, &regimes.transition[regime]);
+                r
+            })
+            .collect()
+    }
+
+    fn two_regimes() -> Regimes {
+        Regimes::new(&[1e-6, 9e-6], &[vec![0.99, 0.01], vec![0.05, 0.95]]).unwrap()
+    }
+
+    #[test]
+    fn recovers_two_regimes() {
+        let truth = two_regimes();
+        let returns = simulate(&truth, 50_000, 5);
+        let fit = fit_regimes(&returns, 2, 500).unwrap();
+        assert!(fit.converged);
+        for j in 0..2 {
+            let ratio = fit.regimes.variances[j] / truth.variances[j];
+            assert!((ratio - 1.0).abs() < 0.1, "variance {j}: {ratio}");
+            let stay = fit.regimes.transition[j][j];
+            assert!(
+                (stay - truth.transition[j][j]).abs() < 0.02,
+                "stay {j}: {stay}"
+            );
+        }
+    }
+
+    #[test]
+    fn reports_the_likelihood_of_the_returned_parameters() {
+        let returns = simulate(&two_regimes(), 20_000, 6);
+        // One iteration leaves the parameters far from the start, so the
+        // E-step's likelihood would not match them.
+        let fit = fit_regimes(&returns, 2, 1).unwrap();
+        let mut filtered = vec![[0.0; MAX_REGIMES]; returns.len()];
+        let ll = hamilton(&fit.regimes, [0.5, 0.5, 0.0], &returns, &mut filtered);
+        assert!(
+            (fit.log_likelihood - ll).abs() < 1.0,
+            "{} vs {ll}",
+            fit.log_likelihood
+        );
+        let last = filtered[returns.len() - 1];
+        for (got, want) in fit.filtered.iter().zip(last) {
+            assert!((got - want).abs() < 1e-9);
+        }
+    }
+}
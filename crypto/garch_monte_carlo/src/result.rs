@@ -0,0 +1,392 @@
+// garch_monte_carlo/src/result.rs
+// Probability estimates returned to Python.
+
+use crate::array;
+use crate::stats::{Moments, Tally};
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Two-sided 95% standard normal quantile.
+const Z_95: f64 = 1.959_963_984_540_054;
+
+#[pyclass(get_all)]
+#[derive(Clone, Debug)]
+pub struct SimulationResult {
+    pub probability: f64,
+    /// Binomial standard error sqrt(p(1-p)/n) for plain hit counts.
+    pub std_error: f64,
+    /// 95% interval: Wilson score for plain hit counts, normal otherwise.
+    pub ci_low: f64,
+    pub ci_high: f64,
+    pub num_simulations: usize,
+    pub elapsed_secs: f64,
+    /// Seed that reproduces this estimate, also when none was passed in.
+    pub seed: u64,
+    /// How many times more paths plain Monte Carlo would need for the same
+    /// standard error, when variance reduction was used.
+    pub variance_reduction: Option<f64>,
+}
+
+impl SimulationResult {
+    pub(crate) fn from_hits(
+        hits: usize,
+        num_simulations: usize,
+        seed: u64,
+        elapsed: Duration,
+    ) -> Self {
+        let n = num_simulations as f64;
+        let p = if num_simulations > 0 {
+            hits as f64 / n
+        } else {
+            f64::NAN
+        };
+        let (ci_low, ci_high) = wilson_interval(p, n, Z_95);
+
+        SimulationResult {
+            probability: p,
+            std_error: (p * (1.0 - p) / n).sqrt(),
+            ci_low,
+            ci_high,
+            num_simulations,
+            elapsed_secs: elapsed.as_secs_f64(),
+            seed,
+            variance_reduction: None,
+        }
+    }
+
+    /// Estimate from per-path values in [0, 1] that are not plain hits,
+    /// with a normal-approximation interval.
+    pub(crate) fn from_tally(
+        tally: &Tally,
+        num_simulations: usize,
+        seed: u64,
+        elapsed: Duration,
+    ) -> Self {
+        let p = tally.mean(num_simulations);
+        let std_error = tally.std_error(num_simulations);
+
+        SimulationResult {
+            probability: p,
+            std_error,
+            ci_low: (p - Z_95 * std_error).max(0.0),
+            ci_high: (p + Z_95 * std_error).min(1.0),
+            num_simulations,
+            elapsed_secs: elapsed.as_secs_f64(),
+            seed,
+            variance_reduction: None,
+        }
+    }
+
+    /// Variance-reduced estimate `p` with standard error `std_error` from
+    /// `num_simulations` paths, and a normal-approximation interval.
+    pub(crate) fn from_estimate(
+        p: f64,
+        std_error: f64,
+        num_simulations: usize,
+        seed: u64,
+        elapsed: Duration,
+        variance_reduction: f64,
+    ) -> Self {
+        SimulationResult {
+            probability: p,
+            std_error,
+            ci_low: (p - Z_95 * std_error).max(0.0),
+            ci_high: (p + Z_95 * std_error).min(1.0),
+            num_simulations,
+            elapsed_secs: elapsed.as_secs_f64(),
+            seed,
+            variance_reduction: variance_reduction.is_finite().then_some(variance_reduction),
+        }
+    }
+}
+
+fn wilson_interval(p: f64, n: f64, z: f64) -> (f64, f64) {
+    let z2 = z * z;
+    let denom = 1.0 + z2 / n;
+    let center = (p + z2 / (2.0 * n)) / denom;
+    let half_width = z / denom * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    (
+        (center - half_width).max(0.0),
+        (center + half_width).min(1.0),
+    )
+}
+
+#[pymethods]
+impl SimulationResult {
+    fn __float__(&self) -> f64 {
+        self.probability
+    }
+
+    /// Standard error relative to the smaller of `p` and `1 - p`: the
+    /// precision that matters for probabilities near 0 or 1.
+    #[getter]
+    fn relative_error(&self) -> f64 {
+        self.std_error / self.probability.min(1.0 - self.probability)
+    }
+
+    fn __repr__(&self) -> String {
+        let reduction = self
+            .variance_reduction
+            .map_or(String::new(), |f| format!(", variance_reduction={f:.2}"));
+        format!(
+            "SimulationResult(probability={:.6}, std_error={:.6}, ci=[{:.6}, {:.6}], num_simulations={}, elapsed_secs={:.4}, seed={}{})",
+            self.probability, self.std_error, self.ci_low, self.ci_high, self.num_simulations, self.elapsed_secs, self.seed, reduction
+        )
+    }
+}
+
+/// One `SimulationResult` per strike, all from the same paths.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct LadderResult {
+    target_prices: Vec<f64>,
+    results: Vec<SimulationResult>,
+}
+
+impl LadderResult {
+    pub(crate) fn new(target_prices: Vec<f64>, results: Vec<SimulationResult>) -> Self {
+        LadderResult {
+            target_prices,
+            results,
+        }
+    }
+
+    fn column(&self, py: Python<'_>, field: fn(&SimulationResult) -> f64) -> PyResult<PyObject> {
+        array::to_numpy(py, self.results.iter().map(field).collect())
+    }
+}
+
+#[pymethods]
+impl LadderResult {
+    #[getter]
+    fn target_prices(&self, py: Python<'_>) -> PyResult<PyObject> {
+        array::to_numpy(py, self.target_prices.clone())
+    }
+
+    /// P(price > target) for each target, i.e. one minus the CDF.
+    #[getter]
+    fn probabilities(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.column(py, |r| r.probability)
+    }
+
+    #[getter]
+    fn std_errors(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.column(py, |r| r.std_error)
+    }
+
+    #[getter]
+    fn ci_low(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.column(py, |r| r.ci_low)
+    }
+
+    #[getter]
+    fn ci_high(&self, py: Python<'_>) -> PyResult<PyObject> {
+        self.column(py, |r| r.ci_high)
+    }
+
+    fn __len__(&self) -> usize {
+        self.results.len()
+    }
+
+    fn __getitem__(&self, index: usize) -> PyResult<SimulationResult> {
+        self.results
+            .get(index)
+            .cloned()
+            .ok_or_else(|| PyIndexError::new_err("strike index out of range"))
+    }
+}
+
+/// Summary of simulated terminal prices.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct DistributionResult {
+    #[pyo3(get)]
+    pub mean: f64,
+    #[pyo3(get)]
+    pub variance: f64,
+    #[pyo3(get)]
+    pub skewness: f64,
+    /// Excess kurtosis.
+    #[pyo3(get)]
+    pub kurtosis: f64,
+    #[pyo3(get)]
+    pub num_simulations: usize,
+    #[pyo3(get)]
+    pub elapsed_secs: f64,
+    #[pyo3(get)]
+    pub seed: u64,
+    quantile_levels: Vec<f64>,
+    quantiles: Vec<f64>,
+    /// `(counts, edges)` when a histogram was requested.
+    histogram: Option<(Vec<f64>, Vec<f64>)>,
+}
+
+impl DistributionResult {
+    pub(crate) fn new(
+        moments: Moments,
+        quantile_levels: Vec<f64>,
+        quantiles: Vec<f64>,
+        histogram: Option<(Vec<f64>, Vec<f64>)>,
+        num_simulations: usize,
+        seed: u64,
+        elapsed: Duration,
+    ) -> Self {
+        DistributionResult {
+            mean: moments.mean,
+            variance: moments.variance,
+            skewness: moments.skewness,
+            kurtosis: moments.kurtosis,
+            num_simulations,
+            elapsed_secs: elapsed.as_secs_f64(),
+            seed,
+            quantile_levels,
+            quantiles,
+            histogram,
+        }
+    }
+}
+
+#[pymethods]
+impl DistributionResult {
+    #[getter]
+    fn quantile_levels(&self, py: Python<'_>) -> PyResult<PyObject> {
+        array::to_numpy(py, self.quantile_levels.clone())
+    }
+
+    /// Terminal price at each of `quantile_levels`.
+    #[getter]
+    fn quantiles(&self, py: Python<'_>) -> PyResult<PyObject> {
+        array::to_numpy(py, self.quantiles.clone())
+    }
+
+    /// Paths per bin, or `None` without a histogram.
+    #[getter]
+    fn histogram(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        self.histogram
+            .as_ref()
+            .map(|(counts, _)| array::to_numpy(py, counts.clone()))
+            .transpose()
+    }
+
+    /// `bins + 1` bin edges in price, or `None` without a histogram.
+    #[getter]
+    fn bin_edges(&self, py: Python<'_>) -> PyResult<Option<PyObject>> {
+        self.histogram
+            .as_ref()
+            .map(|(_, edges)| array::to_numpy(py, edges.clone()))
+            .transpose()
+    }
+}
+
+/// Joint up/down outcomes of several assets from the same paths.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct JointResult {
+    names: Vec<String>,
+    /// Paths per outcome, bit `i` of the index set when asset `i` ended
+    /// above its target.
+    counts: Vec<usize>,
+    #[pyo3(get)]
+    pub num_simulations: usize,
+    elapsed: Duration,
+    #[pyo3(get)]
+    pub seed: u64,
+}
+
+impl JointResult {
+    pub(crate) fn new(
+        names: Vec<String>,
+        counts: Vec<usize>,
+        num_simulations: usize,
+        seed: u64,
+        elapsed: Duration,
+    ) -> Self {
+        JointResult {
+            names,
+            counts,
+            num_simulations,
+            elapsed,
+            seed,
+        }
+    }
+
+    /// Estimate over the outcomes whose bits under `mask` equal `bits`.
+    fn matching(&self, mask: usize, bits: usize) -> SimulationResult {
+        let hits = self
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|&(outcome, _)| outcome & mask == bits)
+            .map(|(_, count)| count)
+            .sum();
+        SimulationResult::from_hits(hits, self.num_simulations, self.seed, self.elapsed)
+    }
+}
+
+#[pymethods]
+impl JointResult {
+    #[getter]
+    fn names(&self) -> Vec<String> {
+        self.names.clone()
+    }
+
+    #[getter]
+    fn elapsed_secs(&self) -> f64 {
+        self.elapsed.as_secs_f64()
+    }
+
+    /// P(all of `outcome` at once), where `outcome` maps asset names to
+    /// `True` for ending above the target and `False` for not; assets left
+    /// out may end either way. `{"BTC": True, "ETH": False}` is "BTC up and
+    /// ETH down".
+    fn probability(&self, outcome: HashMap<String, bool>) -> PyResult<SimulationResult> {
+        let (mut mask, mut bits) = (0, 0);
+        for (name, above) in outcome {
+            let i = self
+                .names
+                .iter()
+                .position(|n| *n == name)
+                .ok_or_else(|| PyValueError::new_err(format!("unknown asset {name:?}")))?;
+            mask |= 1 << i;
+            bits |= (above as usize) << i;
+        }
+        Ok(self.matching(mask, bits))
+    }
+
+    /// P(price > target) for each asset on its own.
+    #[getter]
+    fn marginals(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let marginals = (0..self.names.len())
+            .map(|i| self.matching(1 << i, 1 << i).probability)
+            .collect();
+        array::to_numpy(py, marginals)
+    }
+
+    /// Every combination of outcomes as `(above, probability)`, `above`
+    /// holding one bool per asset in `names` order.
+    fn outcomes(&self) -> Vec<(Vec<bool>, f64)> {
+        let n = self.num_simulations as f64;
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(outcome, &count)| {
+                let above = (0..self.names.len())
+                    .map(|i| outcome >> i & 1 == 1)
+                    .collect();
+                (above, count as f64 / n)
+            })
+            .collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "JointResult(names={:?}, num_simulations={}, elapsed_secs={:.4}, seed={})",
+            self.names,
+            self.num_simulations,
+            self.elapsed.as_secs_f64(),
+            self.seed
+        )
+    }
+}
@@ -0,0 +1,261 @@
+// garch_monte_carlo/src/seasonality.rs
+// Minute-of-week volatility profile.
+
+use crate::array::{self, F64Array};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MINUTES_PER_DAY: usize = 24 * 60;
+const MINUTES_PER_WEEK: usize = 7 * MINUTES_PER_DAY;
+
+/// Minute of the week of a Unix time in seconds, counting from Monday
+/// 00:00 UTC. The epoch fell on a Thursday.
+fn minute_of_week(unix_seconds: f64) -> usize {
+    let minute = (unix_seconds / 60.0).floor() as i64 + 3 * MINUTES_PER_DAY as i64;
+    minute.rem_euclid(MINUTES_PER_WEEK as i64) as usize
+}
+
+/// Current Unix time in seconds.
+pub(crate) fn unix_now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64())
+}
+
+/// Open time of the last minute to have closed at `unix_seconds`.
+pub(crate) fn last_closed_minute(unix_seconds: f64) -> f64 {
+    (unix_seconds / 60.0).floor() * 60.0 - 60.0
+}
+
+/// Wall-clock override that simulations read without a lock, so setting
+/// it never waits on (or fails against) one that is running. NaN stands
+/// for none set.
+#[derive(Debug)]
+pub(crate) struct Clock(AtomicU64);
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock(AtomicU64::new(f64::NAN.to_bits()))
+    }
+}
+
+impl Clock {
+    pub(crate) fn get(&self) -> Option<f64> {
+        let now = f64::from_bits(self.0.load(Ordering::Relaxed));
+        (!now.is_nan()).then_some(now)
+    }
+
+    pub(crate) fn set(&self, now: Option<f64>) -> PyResult<()> {
+        let now = match now {
+            Some(now) if !now.is_finite() => {
+                return Err(PyValueError::new_err("now must be a finite Unix time"))
+            }
+            Some(now) => now,
+            None => f64::NAN,
+        };
+        self.0.store(now.to_bits(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// The override, or the system clock without one.
+    pub(crate) fn now(&self) -> f64 {
+        self.get().unwrap_or_else(unix_now)
+    }
+}
+
+/// Relative volatility of each minute of the week (UTC), scaled so that
+/// the factors' mean square is one. A return divided by its minute's
+/// factor is deseasonalized.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct Seasonality {
+    factors: Vec<f64>,
+}
+
+impl Seasonality {
+    /// Volatility factor of the minute containing `unix_seconds`.
+    pub(crate) fn factor(&self, unix_seconds: f64) -> f64 {
+        self.factors[minute_of_week(unix_seconds)]
+    }
+}
+
+#[pymethods]
+impl Seasonality {
+    /// Estimates the profile from 1-minute `returns` opened at `timestamps`
+    /// (Unix seconds). Each minute's volatility is the mean absolute return
+    /// over that minute of the week and `smoothing_minutes` either side,
+    /// across all weeks; absolute rather than squared returns keep single
+    /// spikes from dominating a bucket.
+    #[staticmethod]
+    #[pyo3(signature = (timestamps, returns, smoothing_minutes=15))]
+    fn estimate(
+        timestamps: F64Array,
+        returns: F64Array,
+        smoothing_minutes: usize,
+    ) -> PyResult<Self> {
+        let (timestamps, returns) = (timestamps.as_slice(), returns.as_slice());
+        if timestamps.len() != returns.len() || returns.is_empty() {
+            return Err(PyValueError::new_err(
+                "need equally many timestamps and returns, at least one",
+            ));
+        }
+        let mut sums = vec![0.0; MINUTES_PER_WEEK];
+        let mut counts = vec![0.0; MINUTES_PER_WEEK];
+        for (&t, &r) in timestamps.iter().zip(returns) {
+            if r.is_finite() {
+                let m = minute_of_week(t);
+                sums[m] += r.abs();
+                counts[m] += 1.0;
+            }
+        }
+        let overall = sums.iter().sum::<f64>() / counts.iter().sum::<f64>();
+        if overall.is_nan() || overall <= 0.0 {
+            return Err(PyValueError::new_err("returns are all zero"));
+        }
+
+        let half = smoothing_minutes.min(MINUTES_PER_WEEK / 2) as isize;
+        let week = MINUTES_PER_WEEK as isize;
+        let mut factors: Vec<f64> = (0..week)
+            .map(|m| {
+                let (mut sum, mut count) = (0.0, 0.0);
+                for d in -half..=half {
+                    let i = (m + d).rem_euclid(week) as usize;
+                    sum += sums[i];
+                    count += counts[i];
+                }
+                if count > 0.0 && sum > 0.0 {
+                    sum / count
+                } else {
+                    overall
+                }
+            })
+            .collect();
+        let rms = (factors.iter().map(|f| f * f).sum::<f64>() / factors.len() as f64).sqrt();
+        factors.iter_mut().for_each(|f| *f /= rms);
+        Ok(Seasonality { factors })
+    }
+
+    /// The profile from 10080 factors, Monday 00:00 UTC first, as they are.
+    #[staticmethod]
+    fn from_factors(factors: F64Array) -> PyResult<Self> {
+        let factors = factors.into_vec();
+        if factors.len() != MINUTES_PER_WEEK || factors.iter().any(|f| f.is_nan() || *f <= 0.0) {
+            return Err(PyValueError::new_err(
+                "need 10080 positive factors, one per minute of the week",
+            ));
+        }
+        Ok(Seasonality { factors })
+    }
+
+    /// One factor per minute of the week, Monday 00:00 UTC first.
+    #[getter]
+    fn factors(&self, py: Python<'_>) -> PyResult<PyObject> {
+        array::to_numpy(py, self.factors.clone())
+    }
+
+    /// Factor of the minute containing each of `timestamps`.
+    #[pyo3(name = "factor")]
+    fn factor_py(&self, py: Python<'_>, timestamps: F64Array) -> PyResult<PyObject> {
+        let factors = timestamps.as_slice().iter().map(|&t| self.factor(t));
+        array::to_numpy(py, factors.collect())
+    }
+
+    /// `returns` opened at `timestamps`, each divided by its minute's
+    /// factor. Fit GARCH to these, or bootstrap them, before simulating
+    /// with this profile.
+    fn deseasonalize(
+        &self,
+        py: Python<'_>,
+        timestamps: F64Array,
+        returns: F64Array,
+    ) -> PyResult<PyObject> {
+        let (timestamps, returns) = (timestamps.as_slice(), returns.as_slice());
+        if timestamps.len() != returns.len() {
+            return Err(PyValueError::new_err(
+                "need equally many timestamps and returns",
+            ));
+        }
+        let deseasonalized = timestamps
+            .iter()
+            .zip(returns)
+            .map(|(&t, &r)| r / self.factor(t))
+            .collect();
+        array::to_numpy(py, deseasonalized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine;
+    use crate::model::{Horizon, Model};
+    use std::sync::Arc;
+
+    /// Monday 1970-01-05 00:00 UTC.
+    const MONDAY: f64 = 4.0 * 86_400.0;
+
+    #[test]
+    fn minutes_count_from_monday_utc() {
+        assert_eq!(minute_of_week(MONDAY), 0);
+        assert_eq!(minute_of_week(MONDAY + 59.9), 0);
+        assert_eq!(minute_of_week(MONDAY + 60.0), 1);
+        assert_eq!(minute_of_week(MONDAY - 1.0), MINUTES_PER_WEEK - 1);
+        assert_eq!(minute_of_week(0.0), 3 * MINUTES_PER_DAY);
+        assert_eq!(minute_of_week(MONDAY + 7.0 * 86_400.0 + 61.0), 1);
+    }
+
+    /// Three weeks of 1-minute returns of size 1, except 3 on Mondays at
+    /// 09:00 UTC.
+    fn profile() -> Seasonality {
+        let (timestamps, returns): (Vec<f64>, Vec<f64>) = (0..3 * MINUTES_PER_WEEK)
+            .map(|m| {
+                let t = MONDAY + 60.0 * m as f64;
+                let size = if m % MINUTES_PER_WEEK == 9 * 60 {
+                    3.0
+                } else {
+                    1.0
+                };
+                (t, if m % 2 == 0 { size } else { -size })
+            })
+            .unzip();
+        Seasonality::estimate(F64Array::Owned(timestamps), F64Array::Owned(returns), 0).unwrap()
+    }
+
+    #[test]
+    fn last_closed_minute_opened_a_minute_before_the_current_one() {
+        assert_eq!(last_closed_minute(MONDAY + 60.0), MONDAY);
+        assert_eq!(last_closed_minute(MONDAY + 119.9), MONDAY);
+        assert_eq!(last_closed_minute(MONDAY + 125.0), MONDAY + 60.0);
+    }
+
+    #[test]
+    fn buckets_returns_by_minute_of_week() {
+        let profile = profile();
+        let later_monday = MONDAY + 5.0 * 7.0 * 86_400.0;
+        let peak = profile.factor(later_monday + 9.0 * 3600.0 + 30.0);
+        let before = profile.factor(later_monday + 9.0 * 3600.0 - 30.0);
+        let tuesday = profile.factor(later_monday + 86_400.0 + 9.0 * 3600.0 + 30.0);
+        assert!((peak / before - 3.0).abs() < 1e-12);
+        assert!((tuesday / before - 1.0).abs() < 1e-12);
+        let mean_square =
+            profile.factors.iter().map(|f| f * f).sum::<f64>() / MINUTES_PER_WEEK as f64;
+        assert!((mean_square - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn deseasonalized_return_round_trips_through_a_simulated_minute() {
+        let profile = profile();
+        let opened = MONDAY + 9.0 * 3600.0;
+        let r = 0.002;
+        let mut model = Model::bootstrap(vec![r / profile.factor(opened)]).unwrap();
+        model.seasonality = Some(Arc::new(profile));
+        let horizon = Horizon {
+            start: Some(opened),
+            ..Horizon::from_seconds(60.0).unwrap()
+        };
+        let mut rng = engine::path_rng(1, 0);
+        assert!((model.sample_log_return(&mut rng, horizon) - r).abs() < 1e-15);
+    }
+}
@@ -0,0 +1,693 @@
+// garch_monte_carlo/src/simulator.rs
+// Long-lived simulator that keeps the resampling pool on the Rust side.
+
+use crate::array::{self, F64Array};
+use crate::block::Blocks;
+use crate::engine::Run;
+use crate::har::{self, Har};
+use crate::heston::Heston;
+use crate::innovations::{Innovations, SkewT};
+use crate::jumps::Jumps;
+use crate::model::{Dynamics, Horizon, Model};
+use crate::reduction::Reduction;
+use crate::regime::{Regimes, MAX_REGIMES};
+use crate::result::{DistributionResult, LadderResult, SimulationResult};
+use crate::seasonality::{self, Clock, Seasonality};
+use crate::variance::VarianceModel;
+use pyo3::exceptions::PyValueError;
+use pyo3::panic::PanicException;
+use pyo3::prelude::*;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Quantile levels reported by `terminal_distribution` unless given.
+const DEFAULT_QUANTILES: [f64; 9] = [0.01, 0.05, 0.1, 0.25, 0.5, 0.75, 0.9, 0.95, 0.99];
+
+/// Holds returns (or GARCH residuals and state) across calls, so the bot
+/// converts its history once and then feeds in one return per minute.
+#[pyclass]
+pub struct Simulator {
+    /// Each simulation takes a snapshot before releasing the GIL, so
+    /// updates only ever need `&self` and never wait on a running one;
+    /// `append_return` copies on write if one still holds the snapshot.
+    model: Mutex<Arc<Model<'static>>>,
+    /// Read without the lock, so it can be set while simulations run.
+    now: Clock,
+}
+
+impl Simulator {
+    pub(crate) fn new(model: Model<'static>) -> Self {
+        Simulator {
+            model: Mutex::new(Arc::new(model)),
+            now: Clock::default(),
+        }
+    }
+
+    /// Snapshot of the model simulations run on, unaffected by later
+    /// updates.
+    pub(crate) fn model(&self) -> Arc<Model<'static>> {
+        Arc::clone(&self.model.lock().unwrap())
+    }
+
+    /// Applies `update` to the model, copying it first if a simulation
+    /// still holds a snapshot.
+    fn update<T>(&self, update: impl FnOnce(&mut Model<'static>) -> T) -> T {
+        update(Arc::make_mut(&mut self.model.lock().unwrap()))
+    }
+
+    /// `horizon` starting at the current wall-clock time, which only
+    /// matters with a seasonality profile.
+    fn clocked(&self, model: &Model, horizon: Horizon) -> Horizon {
+        Horizon {
+            start: model.seasonality.is_some().then(|| self.now.now()),
+            ..horizon
+        }
+    }
+
+    fn filtered(
+        variance: VarianceModel,
+        residuals: F64Array,
+        last_resid: f64,
+        last_sigma_sq: f64,
+        window: Option<usize>,
+    ) -> PyResult<Self> {
+        let mut model = Model::filtered(variance, residuals.into_vec(), last_resid, last_sigma_sq)?;
+        model.window = window;
+        Ok(Simulator::new(model))
+    }
+}
+
+#[pymethods]
+impl Simulator {
+    /// Bootstrap of raw 1-minute log returns, like `calculate_probability_plain`.
+    #[staticmethod]
+    #[pyo3(signature = (returns, window=None))]
+    fn plain(returns: F64Array, window: Option<usize>) -> PyResult<Self> {
+        let mut model = Model::bootstrap(returns.into_vec())?;
+        model.window = window;
+        Ok(Simulator::new(model))
+    }
+
+    /// GARCH(1,1) filtered historical simulation, like
+    /// `calculate_probability_only`.
+    #[staticmethod]
+    #[pyo3(signature = (omega, alpha, beta, residuals, last_resid, last_sigma_sq, window=None))]
+    fn garch(
+        omega: f64,
+        alpha: f64,
+        beta: f64,
+        residuals: F64Array,
+        last_resid: f64,
+        last_sigma_sq: f64,
+        window: Option<usize>,
+    ) -> PyResult<Self> {
+        let garch = VarianceModel::Garch { omega, alpha, beta };
+        Simulator::filtered(garch, residuals, last_resid, last_sigma_sq, window)
+    }
+
+    /// GJR-GARCH(1,1) filtered historical simulation: negative residuals add
+    /// `gamma` to `alpha`.
+    #[staticmethod]
+    #[pyo3(signature = (
+        omega, alpha, gamma, beta, residuals, last_resid, last_sigma_sq, window=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn gjr_garch(
+        omega: f64,
+        alpha: f64,
+        gamma: f64,
+        beta: f64,
+        residuals: F64Array,
+        last_resid: f64,
+        last_sigma_sq: f64,
+        window: Option<usize>,
+    ) -> PyResult<Self> {
+        let gjr = VarianceModel::GjrGarch {
+            omega,
+            alpha,
+            gamma,
+            beta,
+        };
+        Simulator::filtered(gjr, residuals, last_resid, last_sigma_sq, window)
+    }
+
+    /// EGARCH(1,1) filtered historical simulation on the log variance:
+    /// `omega + alpha (|z| - sqrt(2/pi)) + gamma z + beta ln(sigma_sq)`.
+    #[staticmethod]
+    #[pyo3(signature = (
+        omega, alpha, gamma, beta, residuals, last_resid, last_sigma_sq, window=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn egarch(
+        omega: f64,
+        alpha: f64,
+        gamma: f64,
+        beta: f64,
+        residuals: F64Array,
+        last_resid: f64,
+        last_sigma_sq: f64,
+        window: Option<usize>,
+    ) -> PyResult<Self> {
+        let egarch = VarianceModel::Egarch {
+            omega,
+            alpha,
+            gamma,
+            beta,
+        };
+        Simulator::filtered(egarch, residuals, last_resid, last_sigma_sq, window)
+    }
+
+    /// Heston stochastic volatility with variance `v0` for the next minute,
+    /// mean reversion `kappa` per minute to `theta`, vol-of-variance `xi`
+    /// and price/variance correlation `rho`, resampling standardized
+    /// `residuals` for the price shock. Simulated by full-truncation Euler
+    /// at the horizon's steps (minutes).
+    #[staticmethod]
+    #[pyo3(signature = (kappa, theta, xi, rho, v0, residuals, window=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn heston(
+        kappa: f64,
+        theta: f64,
+        xi: f64,
+        rho: f64,
+        v0: f64,
+        residuals: F64Array,
+        window: Option<usize>,
+    ) -> PyResult<Self> {
+        let heston = Heston::new(kappa, theta, xi, rho)?;
+        let mut model = Model::heston(heston, residuals.into_vec(), v0)?;
+        model.window = window;
+        Ok(Simulator::new(model))
+    }
+
+    /// Markov regime switching between `variances` (of a 1-minute return)
+    /// with `transition[i][j]` = P(next regime j | regime i), starting from
+    /// regime `probabilities` for the next minute and resampling
+    /// standardized `residuals`. Each path draws its starting regime and
+    /// switches at every minute close.
+    #[staticmethod]
+    #[pyo3(signature = (variances, transition, probabilities, residuals, window=None))]
+    fn regime_switching(
+        variances: Vec<f64>,
+        transition: Vec<Vec<f64>>,
+        probabilities: Vec<f64>,
+        residuals: F64Array,
+        window: Option<usize>,
+    ) -> PyResult<Self> {
+        let regimes = Regimes::new(&variances, &transition)?;
+        let total: f64 = probabilities.iter().sum();
+        if probabilities.len() != regimes.k
+            || probabilities.iter().any(|p| p.is_nan() || *p < 0.0)
+            || total <= 0.0
+        {
+            return Err(PyValueError::new_err(
+                "need one non-negative probability per regime",
+            ));
+        }
+        let mut probs = [0.0; MAX_REGIMES];
+        for (p, given) in probs.iter_mut().zip(&probabilities) {
+            *p = given / total;
+        }
+        let mut model = Model::regimes(regimes, residuals.into_vec(), probs)?;
+        model.window = window;
+        Ok(Simulator::new(model))
+    }
+
+    /// HAR-RV with `coefficients` `[intercept, five_minute, hourly, daily]`
+    /// on mean squared 1-minute returns, started from `recent_returns` (at
+    /// least the last day of 1-minute log returns), scaling standardized
+    /// `residuals` by the forecast variance of each simulated minute.
+    #[staticmethod]
+    #[pyo3(signature = (coefficients, recent_returns, residuals, window=None))]
+    fn har(
+        coefficients: Vec<f64>,
+        recent_returns: F64Array,
+        residuals: F64Array,
+        window: Option<usize>,
+    ) -> PyResult<Self> {
+        let har = Har::new(har::coefficients(&coefficients)?, recent_returns.as_slice())?;
+        let mut model = Model::har(har, residuals.into_vec())?;
+        model.window = window;
+        Ok(Simulator::new(model))
+    }
+
+    /// P(price > target_price) after `horizon_seconds`. A horizon that is not
+    /// a whole number of minutes starts with the rest of the current minute
+    /// as one variance-scaled partial step.
+    ///
+    /// `antithetic` simulates mirrored pairs of paths and `control_variate`
+    /// regresses out a lognormal digital on the same shocks.
+    /// `importance_sampling`, for strikes far in the tails, draws shocks
+    /// tilted toward the strike and reweights each path by its likelihood
+    /// ratio; it does not combine with the other two. Each sets the
+    /// result's `variance_reduction`, and all need i.i.d. resampling.
+    #[pyo3(signature = (
+        current_price, target_price, horizon_seconds, num_simulations, seed=None,
+        target_std_error=None, max_seconds=None, antithetic=false, control_variate=false, importance_sampling=false
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn probability(
+        &self,
+        py: Python<'_>,
+        current_price: f64,
+        target_price: f64,
+        horizon_seconds: f64,
+        num_simulations: usize,
+        seed: Option<u64>,
+        target_std_error: Option<f64>,
+        max_seconds: Option<f64>,
+        antithetic: bool,
+        control_variate: bool,
+        importance_sampling: bool,
+    ) -> PyResult<SimulationResult> {
+        let run = Run::new(num_simulations, seed, target_std_error, max_seconds)?;
+        let model = self.model();
+        let horizon = self.clocked(&model, Horizon::from_seconds(horizon_seconds)?);
+        let reduction = Reduction {
+            antithetic,
+            control_variate,
+            importance: importance_sampling,
+        };
+        py.allow_threads(|| {
+            model.probability_with(current_price, target_price, horizon, reduction, &run)
+        })
+    }
+
+    /// `probability` conditioned on the candle in progress: the current
+    /// minute opened `elapsed_seconds` ago and has returned `minute_return`
+    /// (log) so far. Only its remaining part is simulated, and under GARCH
+    /// the next variance uses the full minute's residual, observed plus
+    /// simulated. The plain bootstrap has no state to update.
+    #[pyo3(signature = (
+        current_price, target_price, horizon_seconds, elapsed_seconds, minute_return,
+        num_simulations, seed=None, target_std_error=None, max_seconds=None,
+        antithetic=false, control_variate=false, importance_sampling=false
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn probability_intraminute(
+        &self,
+        py: Python<'_>,
+        current_price: f64,
+        target_price: f64,
+        horizon_seconds: f64,
+        elapsed_seconds: f64,
+        minute_return: f64,
+        num_simulations: usize,
+        seed: Option<u64>,
+        target_std_error: Option<f64>,
+        max_seconds: Option<f64>,
+        antithetic: bool,
+        control_variate: bool,
+        importance_sampling: bool,
+    ) -> PyResult<SimulationResult> {
+        let run = Run::new(num_simulations, seed, target_std_error, max_seconds)?;
+        let model = self.model();
+        let horizon = self.clocked(
+            &model,
+            Horizon::within_minute(horizon_seconds, elapsed_seconds, minute_return)?,
+        );
+        let reduction = Reduction {
+            antithetic,
+            control_variate,
+            importance: importance_sampling,
+        };
+        py.allow_threads(|| {
+            model.probability_with(current_price, target_price, horizon, reduction, &run)
+        })
+    }
+
+    /// `probability` by randomized quasi-Monte Carlo: each simulated
+    /// minute's shock comes from one dimension of an Owen-scrambled Sobol
+    /// point, through the inverse CDF of the innovations (resampled or
+    /// parametric). `num_simulations` is shared out over `scrambles`
+    /// independent scramblings, rounded up to a power of two each; the
+    /// spread of their estimates gives the standard error and the result's
+    /// `variance_reduction` against plain sampling. Needs i.i.d. resampling,
+    /// and takes at most 2048 simulated steps (about 34 hours); longer
+    /// horizons raise ValueError.
+    #[pyo3(signature = (
+        current_price, target_price, horizon_seconds, num_simulations, seed=None,
+        scrambles=16
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn probability_qmc(
+        &self,
+        py: Python<'_>,
+        current_price: f64,
+        target_price: f64,
+        horizon_seconds: f64,
+        num_simulations: usize,
+        seed: Option<u64>,
+        scrambles: usize,
+    ) -> PyResult<SimulationResult> {
+        let run = Run::new(num_simulations, seed, None, None)?;
+        let model = self.model();
+        let horizon = self.clocked(&model, Horizon::from_seconds(horizon_seconds)?);
+        py.allow_threads(|| {
+            model.probability_qmc(
+                current_price,
+                target_price,
+                horizon,
+                num_simulations,
+                scrambles,
+                run.seed,
+                run.started,
+            )
+        })
+    }
+
+    /// `probability` for a whole ladder of strikes on the same expiry, from a
+    /// single set of simulated paths. Adaptive runs stop on the largest
+    /// standard error across strikes.
+    #[pyo3(signature = (
+        current_price, target_prices, horizon_seconds, num_simulations, seed=None,
+        target_std_error=None, max_seconds=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn probability_ladder(
+        &self,
+        py: Python<'_>,
+        current_price: f64,
+        target_prices: F64Array,
+        horizon_seconds: f64,
+        num_simulations: usize,
+        seed: Option<u64>,
+        target_std_error: Option<f64>,
+        max_seconds: Option<f64>,
+    ) -> PyResult<LadderResult> {
+        let run = Run::new(num_simulations, seed, target_std_error, max_seconds)?;
+        let model = self.model();
+        let horizon = self.clocked(&model, Horizon::from_seconds(horizon_seconds)?);
+        let target_prices = target_prices.as_slice();
+        Ok(py.allow_threads(|| {
+            model.probability_ladder(current_price, target_prices, horizon, &run)
+        }))
+    }
+
+    /// Probability of touching `barrier_price` before the horizon ends:
+    /// from below if it is above `current_price`, from above otherwise.
+    #[pyo3(signature = (
+        current_price, barrier_price, horizon_seconds, num_simulations, seed=None,
+        target_std_error=None, max_seconds=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn barrier_probability(
+        &self,
+        py: Python<'_>,
+        current_price: f64,
+        barrier_price: f64,
+        horizon_seconds: f64,
+        num_simulations: usize,
+        seed: Option<u64>,
+        target_std_error: Option<f64>,
+        max_seconds: Option<f64>,
+    ) -> PyResult<SimulationResult> {
+        let run = Run::new(num_simulations, seed, target_std_error, max_seconds)?;
+        let model = self.model();
+        let horizon = self.clocked(&model, Horizon::from_seconds(horizon_seconds)?);
+        Ok(py.allow_threads(|| {
+            model.barrier_probability(current_price, barrier_price, horizon, &run)
+        }))
+    }
+
+    /// Mean, variance, skewness, kurtosis and quantiles of the simulated
+    /// terminal price, plus a `bins`-bin histogram over `hist_range`
+    /// (default: all paths) when `bins` is given.
+    #[pyo3(signature = (
+        current_price, horizon_seconds, num_simulations, seed=None,
+        quantiles=None, bins=None, hist_range=None
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn terminal_distribution(
+        &self,
+        py: Python<'_>,
+        current_price: f64,
+        horizon_seconds: f64,
+        num_simulations: usize,
+        seed: Option<u64>,
+        quantiles: Option<Vec<f64>>,
+        bins: Option<usize>,
+        hist_range: Option<(f64, f64)>,
+    ) -> PyResult<DistributionResult> {
+        if bins == Some(0) {
+            return Err(PyValueError::new_err("bins must be positive"));
+        }
+        let quantile_levels = quantiles.unwrap_or_else(|| DEFAULT_QUANTILES.to_vec());
+        let histogram = bins.map(|bins| {
+            let (lo, hi) = hist_range.unzip();
+            (bins, lo, hi)
+        });
+        let run = Run::new(num_simulations, seed, None, None)?;
+        let model = self.model();
+        let horizon = self.clocked(&model, Horizon::from_seconds(horizon_seconds)?);
+        Ok(py.allow_threads(|| {
+            model.terminal_distribution(current_price, horizon, quantile_levels, histogram, &run)
+        }))
+    }
+
+    /// Same as `probability`, but runs on a background thread and returns a
+    /// `concurrent.futures.Future` at once. Await it from asyncio with
+    /// `asyncio.wrap_future`. Errors, including invalid arguments found
+    /// while simulating, are set as the Future's exception.
+    #[pyo3(signature = (
+        current_price, target_price, horizon_seconds, num_simulations, seed=None,
+        target_std_error=None, max_seconds=None, antithetic=false, control_variate=false, importance_sampling=false
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn probability_future(
+        &self,
+        py: Python<'_>,
+        current_price: f64,
+        target_price: f64,
+        horizon_seconds: f64,
+        num_simulations: usize,
+        seed: Option<u64>,
+        target_std_error: Option<f64>,
+        max_seconds: Option<f64>,
+        antithetic: bool,
+        control_variate: bool,
+        importance_sampling: bool,
+    ) -> PyResult<PyObject> {
+        let run = Run::new(num_simulations, seed, target_std_error, max_seconds)?;
+        let model = self.model();
+        let horizon = self.clocked(&model, Horizon::from_seconds(horizon_seconds)?);
+        let reduction = Reduction {
+            antithetic,
+            control_variate,
+            importance: importance_sampling,
+        };
+        spawn_future(py, move || {
+            model.probability_with(current_price, target_price, horizon, reduction, &run)
+        })
+    }
+
+    /// Adds the latest closed 1-minute log return. Under GARCH this also
+    /// stores its standardized residual and advances the variance. With a
+    /// seasonality profile the return is first deseasonalized by the factor
+    /// of the minute opened at `timestamp` (default: the last minute to
+    /// close before `now`).
+    #[pyo3(signature = (r, timestamp=None))]
+    fn append_return(&self, r: f64, timestamp: Option<f64>) {
+        self.update(|model| {
+            let r = match &model.seasonality {
+                Some(profile) => {
+                    let opened = timestamp
+                        .unwrap_or_else(|| seasonality::last_closed_minute(self.now.now()));
+                    r / profile.factor(opened)
+                }
+                None => r,
+            };
+            model.append_return(r);
+        })
+    }
+
+    /// Rescales every simulated minute by `profile`'s factor for the
+    /// wall-clock minute it represents, or stops with `None`. The shocks
+    /// must be deseasonalized by the same profile: build the simulator from
+    /// `profile.deseasonalize(timestamps, returns)` (or a GARCH fit to
+    /// them).
+    #[pyo3(signature = (profile))]
+    fn set_seasonality(&self, profile: Option<Seasonality>) {
+        self.update(|model| model.seasonality = profile.map(Arc::new));
+    }
+
+    #[getter]
+    fn seasonality(&self) -> Option<Seasonality> {
+        self.model().seasonality.as_deref().cloned()
+    }
+
+    /// Wall-clock Unix time (seconds) simulations start from when a
+    /// seasonality profile is set; `None` reads the system clock. Set it to
+    /// replay history.
+    #[getter]
+    fn now(&self) -> Option<f64> {
+        self.now.get()
+    }
+
+    #[setter]
+    fn set_now(&self, now: Option<f64>) -> PyResult<()> {
+        self.now.set(now)
+    }
+
+    /// Adds compound-Poisson `jumps` to every simulated step, or stops with
+    /// `None`. They do not feed into the variance recursion.
+    #[pyo3(signature = (jumps))]
+    fn set_jumps(&self, jumps: Option<Jumps>) {
+        self.update(|model| model.jumps = jumps);
+    }
+
+    #[getter]
+    fn jumps(&self) -> Option<Jumps> {
+        self.model().jumps
+    }
+
+    /// Draws every simulated shock from a standardized Student-t
+    /// (`distribution="t"`) or Hansen skewed-t (`"skewt"`) instead of
+    /// resampling history, or goes back to `"bootstrap"`. Whichever of `dof`
+    /// and `skew` is not given is fitted by maximum likelihood to the
+    /// current shocks. Returns the `(dof, skew)` in use.
+    #[pyo3(signature = (distribution="bootstrap", dof=None, skew=None))]
+    fn set_innovations(
+        &self,
+        py: Python<'_>,
+        distribution: &str,
+        dof: Option<f64>,
+        skew: Option<f64>,
+    ) -> PyResult<(Option<f64>, Option<f64>)> {
+        let skewed = match distribution.to_ascii_lowercase().as_str() {
+            "bootstrap" => {
+                self.update(|model| model.set_innovations(None));
+                return Ok((None, None));
+            }
+            "t" | "student-t" | "studentt" => false,
+            "skewt" | "skewed-t" | "skew-t" => true,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown innovations {distribution:?}; expected 'bootstrap', 't' or 'skewt'"
+                )))
+            }
+        };
+        let model = self.model();
+        let dist = py.allow_threads(|| SkewT::fit(&model.shocks, skewed, dof, skew))?;
+        self.update(|model| model.set_innovations(Some(dist)));
+        Ok((Some(dist.dof), Some(dist.skew)))
+    }
+
+    /// Resamples runs of consecutive shocks instead of single ones:
+    /// `"stationary"` (geometric lengths of mean `block_length`),
+    /// `"circular"` (fixed `block_length`), or `"iid"` to go back. Without
+    /// `block_length` it is estimated from the current shocks. Returns the
+    /// mean block length in use.
+    #[pyo3(signature = (scheme="stationary", block_length=None))]
+    fn set_block_bootstrap(&self, scheme: &str, block_length: Option<f64>) -> PyResult<f64> {
+        let blocks = Blocks::parse(scheme, block_length, &self.model().shocks)?;
+        self.update(|model| model.blocks = blocks);
+        Ok(blocks.mean_length())
+    }
+
+    /// `(scheme, mean_block_length)` of the bootstrap.
+    #[getter]
+    fn block_bootstrap(&self) -> (&'static str, f64) {
+        let blocks = self.model().blocks;
+        (blocks.name(), blocks.mean_length())
+    }
+
+    /// `(distribution, dof, skew)` of the shocks, with `dof` and `skew`
+    /// `None` for the bootstrap.
+    #[getter]
+    fn innovations(&self) -> (&'static str, Option<f64>, Option<f64>) {
+        let innovations = self.model().innovations;
+        match innovations {
+            Innovations::Bootstrap => (innovations.name(), None, None),
+            Innovations::Parametric { dist, .. } => {
+                (innovations.name(), Some(dist.dof), Some(dist.skew))
+            }
+        }
+    }
+
+    /// Returns (plain) or standardized residuals (GARCH) currently resampled,
+    /// as a NumPy array.
+    #[getter]
+    fn shocks(&self, py: Python<'_>) -> PyResult<PyObject> {
+        array::to_numpy(py, self.model().shocks.to_vec())
+    }
+
+    /// Number of returns or residuals currently resampled.
+    fn __len__(&self) -> usize {
+        self.model().shocks.len()
+    }
+
+    /// Variance of the next minute (GARCH, Heston, HAR, or expected over
+    /// the regimes), or `None` for the plain bootstrap.
+    #[getter]
+    fn sigma_sq(&self) -> Option<f64> {
+        match self.model().dynamics {
+            Dynamics::Bootstrap => None,
+            Dynamics::Filtered { sigma_sq, .. } => Some(sigma_sq),
+            Dynamics::Heston { v, .. } => Some(v),
+            Dynamics::Regime { regimes, probs } => Some(regimes.variance(&probs)),
+            Dynamics::Har { ref har } => Some(har.variance(0)),
+        }
+    }
+
+    /// HAR forecast of the log-return variance over `horizon_seconds`, or
+    /// `None` without HAR dynamics.
+    fn har_forecast(&self, horizon_seconds: f64) -> PyResult<Option<f64>> {
+        let horizon = Horizon::from_seconds(horizon_seconds)?;
+        Ok(match self.model().dynamics {
+            Dynamics::Har { ref har } => Some(har.term_variance(horizon)),
+            _ => None,
+        })
+    }
+
+    /// Filtered probability of each regime for the next minute, calmest
+    /// first, or `None` without regime switching.
+    #[getter]
+    fn regime_probabilities(&self) -> Option<Vec<f64>> {
+        match self.model().dynamics {
+            Dynamics::Regime { regimes, probs } => Some(probs[..regimes.k].to_vec()),
+            _ => None,
+        }
+    }
+}
+
+/// Runs `work` on a new thread without the GIL and resolves the returned
+/// `concurrent.futures.Future` with its output. An error or a panic becomes
+/// the Future's exception, so it always resolves; the Future is
+/// thread-safe, and `asyncio.wrap_future` hands the outcome to the event
+/// loop with `call_soon_threadsafe`.
+pub(crate) fn spawn_future<T, F>(py: Python<'_>, work: F) -> PyResult<PyObject>
+where
+    T: IntoPy<PyObject>,
+    F: FnOnce() -> PyResult<T> + Send + 'static,
+{
+    let future: PyObject = py
+        .import("concurrent.futures")?
+        .getattr("Future")?
+        .call0()?
+        .into();
+    future.call_method0(py, "set_running_or_notify_cancel")?;
+
+    let handle = future.clone_ref(py);
+    thread::spawn(move || {
+        let outcome = panic::catch_unwind(AssertUnwindSafe(work)).unwrap_or_else(|payload| {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "simulation panicked".to_string());
+            Err(PanicException::new_err(message))
+        });
+        Python::with_gil(|py| {
+            let resolved = match outcome {
+                Ok(output) => handle.call_method1(py, "set_result", (output.into_py(py),)),
+                Err(err) => handle.call_method1(py, "set_exception", (err.into_value(py),)),
+            };
+            if let Err(err) = resolved {
+                err.print(py);
+            }
+        });
+    });
+    Ok(future)
+}
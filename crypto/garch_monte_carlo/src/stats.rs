@@ -0,0 +1,142 @@
+// garch_monte_carlo/src/stats.rs
+// Descriptive statistics of samples.
+
+/// Mean, variance and standardized higher moments of a sample.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Moments {
+    pub mean: f64,
+    pub variance: f64,
+    pub skewness: f64,
+    /// Excess kurtosis, zero for a normal distribution.
+    pub kurtosis: f64,
+}
+
+impl Moments {
+    pub(crate) fn of(values: &[f64]) -> Self {
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let (mut m2, mut m3, mut m4) = (0.0, 0.0, 0.0);
+        for &x in values {
+            let d = x - mean;
+            let d2 = d * d;
+            m2 += d2;
+            m3 += d2 * d;
+            m4 += d2 * d2;
+        }
+        let (m2, m3, m4) = (m2 / n, m3 / n, m4 / n);
+
+        Moments {
+            mean,
+            variance: m2,
+            skewness: m3 / m2.powf(1.5),
+            kurtosis: m4 / (m2 * m2) - 3.0,
+        }
+    }
+}
+
+/// Quantile of an ascending sample, interpolating linearly between order
+/// statistics like NumPy's default method.
+pub(crate) fn quantile_sorted(sorted: &[f64], q: f64) -> f64 {
+    let h = (sorted.len() - 1) as f64 * q.clamp(0.0, 1.0);
+    let lo = h.floor() as usize;
+    let hi = (lo + 1).min(sorted.len() - 1);
+    sorted[lo] + (h - lo as f64) * (sorted[hi] - sorted[lo])
+}
+
+/// Counts of `values` in `bins` equal-width bins over `[lo, hi]`, with the
+/// last bin closed. Values outside the range are not counted.
+/// Returns `(counts, edges)`.
+pub(crate) fn histogram(values: &[f64], bins: usize, lo: f64, hi: f64) -> (Vec<f64>, Vec<f64>) {
+    let width = (hi - lo) / bins as f64;
+    let mut counts = vec![0.0; bins];
+    for &x in values {
+        if x < lo || x > hi {
+            continue;
+        }
+        let bin = (((x - lo) / width) as usize).min(bins - 1);
+        counts[bin] += 1.0;
+    }
+    let edges = (0..=bins).map(|i| lo + i as f64 * width).collect();
+    (counts, edges)
+}
+
+/// Running sum and sum of squares of per-path estimates.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Tally {
+    pub sum: f64,
+    pub sum_sq: f64,
+}
+
+impl Tally {
+    pub(crate) fn push(&mut self, x: f64) {
+        self.sum += x;
+        self.sum_sq += x * x;
+    }
+
+    pub(crate) fn merge(self, other: Tally) -> Tally {
+        Tally {
+            sum: self.sum + other.sum,
+            sum_sq: self.sum_sq + other.sum_sq,
+        }
+    }
+
+    pub(crate) fn mean(&self, n: usize) -> f64 {
+        self.sum / n as f64
+    }
+
+    /// Standard error of the mean over `n` samples.
+    pub(crate) fn std_error(&self, n: usize) -> f64 {
+        let n = n as f64;
+        let mean = self.sum / n;
+        let variance = (self.sum_sq / n - mean * mean).max(0.0) * n / (n - 1.0);
+        (variance / n).sqrt()
+    }
+}
+
+/// Running sums for the means, variances and covariance of paired per-path
+/// values.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct PairTally {
+    pub n: usize,
+    sum_x: f64,
+    sum_y: f64,
+    sum_xx: f64,
+    sum_yy: f64,
+    sum_xy: f64,
+}
+
+impl PairTally {
+    pub(crate) fn push(&mut self, x: f64, y: f64) {
+        self.n += 1;
+        self.sum_x += x;
+        self.sum_y += y;
+        self.sum_xx += x * x;
+        self.sum_yy += y * y;
+        self.sum_xy += x * y;
+    }
+
+    pub(crate) fn merge(self, other: PairTally) -> PairTally {
+        PairTally {
+            n: self.n + other.n,
+            sum_x: self.sum_x + other.sum_x,
+            sum_y: self.sum_y + other.sum_y,
+            sum_xx: self.sum_xx + other.sum_xx,
+            sum_yy: self.sum_yy + other.sum_yy,
+            sum_xy: self.sum_xy + other.sum_xy,
+        }
+    }
+
+    /// `(mean_x, mean_y, var_x, var_y, cov)`, the (co)variances unbiased.
+    pub(crate) fn moments(&self) -> (f64, f64, f64, f64, f64) {
+        let n = self.n as f64;
+        let (mx, my) = (self.sum_x / n, self.sum_y / n);
+        let unbias = n / (n - 1.0);
+        (
+            mx,
+            my,
+            (self.sum_xx / n - mx * mx).max(0.0) * unbias,
+            (self.sum_yy / n - my * my).max(0.0) * unbias,
+            (self.sum_xy / n - mx * my) * unbias,
+        )
+    }
+}
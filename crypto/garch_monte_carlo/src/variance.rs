@@ -0,0 +1,125 @@
+// garch_monte_carlo/src/variance.rs
+// Conditional variance recursions for filtered historical simulation.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::f64::consts::FRAC_2_PI;
+
+/// One-minute variance recursion driven by the last residual `ε = σz`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum VarianceModel {
+    /// σ²' = ω + α ε² + β σ²
+    Garch { omega: f64, alpha: f64, beta: f64 },
+    /// σ²' = ω + (α + γ·1[ε < 0]) ε² + β σ², so falls raise variance more.
+    GjrGarch {
+        omega: f64,
+        alpha: f64,
+        gamma: f64,
+        beta: f64,
+    },
+    /// ln σ²' = ω + α (|z| - E|z|) + γ z + β ln σ², with E|z| Gaussian.
+    Egarch {
+        omega: f64,
+        alpha: f64,
+        gamma: f64,
+        beta: f64,
+    },
+}
+
+/// Which recursion to fit, as named from Python.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum VarianceKind {
+    Garch,
+    GjrGarch,
+    Egarch,
+}
+
+impl VarianceKind {
+    pub(crate) fn parse(name: &str) -> PyResult<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "garch" => Ok(VarianceKind::Garch),
+            "gjr" | "gjr-garch" | "gjr_garch" => Ok(VarianceKind::GjrGarch),
+            "egarch" => Ok(VarianceKind::Egarch),
+            _ => Err(PyValueError::new_err(format!(
+                "unknown variance model {name:?}; expected 'garch', 'gjr' or 'egarch'"
+            ))),
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            VarianceKind::Garch => "garch",
+            VarianceKind::GjrGarch => "gjr",
+            VarianceKind::Egarch => "egarch",
+        }
+    }
+}
+
+impl VarianceModel {
+    pub(crate) fn kind(&self) -> VarianceKind {
+        match self {
+            VarianceModel::Garch { .. } => VarianceKind::Garch,
+            VarianceModel::GjrGarch { .. } => VarianceKind::GjrGarch,
+            VarianceModel::Egarch { .. } => VarianceKind::Egarch,
+        }
+    }
+
+    /// Variance of the next minute given this minute's variance and residual.
+    pub(crate) fn next_variance(&self, sigma_sq: f64, resid: f64) -> f64 {
+        match *self {
+            VarianceModel::Garch { omega, alpha, beta } => {
+                omega + alpha * resid * resid + beta * sigma_sq
+            }
+            VarianceModel::GjrGarch {
+                omega,
+                alpha,
+                gamma,
+                beta,
+            } => {
+                let leverage = if resid < 0.0 { gamma } else { 0.0 };
+                omega + (alpha + leverage) * resid * resid + beta * sigma_sq
+            }
+            VarianceModel::Egarch {
+                omega,
+                alpha,
+                gamma,
+                beta,
+            } => {
+                let z = resid / sigma_sq.sqrt();
+                (omega + alpha * (z.abs() - FRAC_2_PI.sqrt()) + gamma * z + beta * sigma_sq.ln())
+                    .exp()
+            }
+        }
+    }
+
+    /// How slowly a variance shock decays: α + γ/2 + β for the GARCH
+    /// family, β for EGARCH (in log variance).
+    pub(crate) fn persistence(&self) -> f64 {
+        match *self {
+            VarianceModel::Garch { alpha, beta, .. } => alpha + beta,
+            VarianceModel::GjrGarch {
+                alpha, gamma, beta, ..
+            } => alpha + 0.5 * gamma + beta,
+            VarianceModel::Egarch { beta, .. } => beta,
+        }
+    }
+
+    /// `(omega, alpha, gamma, beta)`, with `gamma` absent for GARCH.
+    pub(crate) fn params(&self) -> (f64, f64, Option<f64>, f64) {
+        match *self {
+            VarianceModel::Garch { omega, alpha, beta } => (omega, alpha, None, beta),
+            VarianceModel::GjrGarch {
+                omega,
+                alpha,
+                gamma,
+                beta,
+            }
+            | VarianceModel::Egarch {
+                omega,
+                alpha,
+                gamma,
+                beta,
+            } => (omega, alpha, Some(gamma), beta),
+        }
+    }
+}
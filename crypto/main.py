@@ -1,7 +1,8 @@
 import asyncio
 from time import sleep
 import httpx
-from crypto.api.binance import get_latest_bitcoin_price, get_bitcoin_1h_open_price, get_latest_bitcoin_price_async
+from crypto.api.binance import get_latest_bitcoin_price, get_bitcoin_1h_open_price, get_latest_bitcoin_price_async, \
+    WINDOW_SIZE
 from crypto.api.polymarket.account import cancel_order, place_order, get_client, \
     get_my_trade_history_async, get_my_open_orders_async, update_allowances
 from crypto.api.polymarket.get_event import get_current_event
@@ -138,8 +139,8 @@ class MarketMakerBot:
         self.tick_size = 0.01
 
         self.file_lock = threading.Lock()
-        self.candle_manager = CandleManager(self.file_lock, self.read_returns())
-        self.returns = None
+        self.candle_manager = CandleManager(self.file_lock, self.append_returns)
+        self.simulator = None
 
         self.client = get_client()
         self.address = os.getenv("POLYMARKET_PROXY_ADDRESS")
@@ -168,9 +169,6 @@ class MarketMakerBot:
             if secs_left <= 0:
                 self.switch_events()
                 continue
-            mins = secs_left // 60
-            secs = secs_left % 60
-            # print(f"{mins:.0f} Minuten und {secs:.0f} Sekunden verbleibend")
 
             # 440ms
             # fetched_data = get_mock_data()
@@ -181,15 +179,17 @@ class MarketMakerBot:
             my_open_orders = fetched_data[3]
 
             # 70ms
-            self.p_fair = garch_monte_carlo.calculate_probability_plain(
-                returns=self.returns,
+            result = self.simulator.probability(
                 current_price=current_btc_price,
                 target_price=self.open_price,
-                horizon_minutes=max(1, round(mins + secs / 60)),
+                horizon_seconds=secs_left,
                 num_simulations=self.config['NUM_SIMULATIONS'],
+                target_std_error=self.config['TARGET_STD_ERROR'],
+                max_seconds=self.config['MAX_SIMULATION_SECS'],
             )
+            self.p_fair = result.probability
             # self.p_fair = get_mock_p_fair()
-            print(f"p_fair: {self.p_fair}")
+            print(f"p_fair: {self.p_fair} ± {result.std_error:.4f}")
 
             self.min_order_size = float(order_book['min_order_size'])
             self.tick_size = float(order_book['tick_size'])
@@ -379,7 +379,12 @@ class MarketMakerBot:
 
     def read_returns(self):
         with self.file_lock:
-            self.returns = pd.read_csv(FILENAME)["log_return"].dropna().tolist()
+            returns = pd.read_csv(FILENAME)["log_return"].dropna().to_numpy()
+        self.simulator = garch_monte_carlo.Simulator.plain(returns, window=WINDOW_SIZE)
+
+    def append_returns(self, new_returns):
+        for r in new_returns:
+            self.simulator.append_return(r)
 
     def get_my_best_bid_ask(self, best_bid_price, best_ask_price):
         a = 1 / self.tick_size
@@ -704,7 +709,9 @@ if __name__ == "__main__":
         "RISK_THRESHOLD": 0.005, # 0.5 %
         "LIMIT_ORDER_SIZE": 10,
         "LOOP_DELAY_SECS": 0,
-        "NUM_SIMULATIONS": 1_000_000
+        "NUM_SIMULATIONS": 1_000_000, # Upper bound, runs stop early at TARGET_STD_ERROR
+        "TARGET_STD_ERROR": 0.0005,
+        "MAX_SIMULATION_SECS": 0.1,
     }
 
     BOT_CONFIG["MAX_INVENTORY"] = BOT_CONFIG["PORTFOLIO_SIZE"] * BOT_CONFIG["MAX_POSITION_PERCENT"]